@@ -20,7 +20,8 @@ pub struct User {
   #[serde(skip_serializing_if = "Option::is_none")]
   pub password: Option<String>,
   pub role: Role,
-  #[serde(skip, default)]
+  /// Tokens issued before this (unix seconds) are refused
+  #[serde(default)]
   pub valid_after: i64,
 }
 
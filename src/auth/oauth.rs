@@ -3,23 +3,55 @@ use crate::error::AppError;
 use crate::state::AppState;
 use anyhow::{Result, anyhow};
 use axum::{
-  extract::{Query, State},
-  response::{IntoResponse, Redirect},
+  extract::{Path, Query, State},
+  http::StatusCode,
+  response::{IntoResponse, Redirect, Response},
 };
 use oauth2::reqwest;
 use oauth2::{
   AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, EmptyExtraTokenFields,
-  EndpointNotSet, EndpointSet, RedirectUrl, RevocationErrorResponseType, StandardErrorResponse,
-  StandardRevocableToken, StandardTokenIntrospectionResponse, StandardTokenResponse, TokenResponse,
-  TokenUrl,
+  EndpointNotSet, EndpointSet, RedirectUrl, RevocationErrorResponseType, Scope,
+  StandardErrorResponse, StandardRevocableToken, StandardTokenIntrospectionResponse,
+  StandardTokenResponse, TokenResponse, TokenUrl,
   basic::{BasicClient, BasicErrorResponseType, BasicTokenType},
 };
 use std::sync::Arc;
+use strum::Display;
 use tower_cookies::{Cookie, Cookies};
 
 pub const AUTH_URL: &str = "https://codeberg.org/login/oauth/authorize";
 pub const TOKEN_URL: &str = "https://codeberg.org/login/oauth/access_token";
 pub const CODEBERG_API_BASE_URL: &str = "https://codeberg.org/api/v1";
+pub const GITHUB_AUTH_URL: &str = "https://github.com/login/oauth/authorize";
+pub const GITHUB_TOKEN_URL: &str = "https://github.com/login/oauth/access_token";
+pub const GITHUB_USER_INFO_URL: &str = "https://api.github.com/user";
+/// Added to GitHub ids so they can't collide with Codeberg (or local) ids
+pub const GITHUB_ID_OFFSET: i64 = 1 << 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Provider {
+  Codeberg,
+  Github,
+}
+
+impl Provider {
+  fn urls(self) -> (&'static str, &'static str) {
+    match self {
+      Provider::Codeberg => (AUTH_URL, TOKEN_URL),
+      Provider::Github => (GITHUB_AUTH_URL, GITHUB_TOKEN_URL),
+    }
+  }
+
+  /// Map the provider's own user id into the shared id space.
+  fn namespaced_id(self, id: i64) -> i64 {
+    match self {
+      Provider::Codeberg => id,
+      Provider::Github => GITHUB_ID_OFFSET + id,
+    }
+  }
+}
 
 pub type Client = oauth2::Client<
   StandardErrorResponse<BasicErrorResponseType>,
@@ -34,12 +66,17 @@ pub type Client = oauth2::Client<
   EndpointSet,
 >;
 
-pub fn init_oauth_client(config: &crate::conf::Config) -> Client {
-  let client_id = ClientId::new(config.oauth.client_id.clone());
-  let client_secret = ClientSecret::new(config.oauth.client_secret.clone());
-  let auth_url = AuthUrl::new(AUTH_URL.to_string()).expect("Invalid authorization endpoint URL");
-  let token_url = TokenUrl::new(TOKEN_URL.to_string()).expect("Invalid token endpoint URL");
-  let redirect_url = RedirectUrl::new(config.domain.to_string() + "/oauth-callback/codeberg")
+pub fn init_oauth_client(
+  config: &crate::conf::Config,
+  provider: Provider,
+  oauth: &crate::conf::OAuthConfig,
+) -> Client {
+  let (auth_url, token_url) = provider.urls();
+  let client_id = ClientId::new(oauth.client_id.clone());
+  let client_secret = ClientSecret::new(oauth.client_secret.clone());
+  let auth_url = AuthUrl::new(auth_url.to_string()).expect("Invalid authorization endpoint URL");
+  let token_url = TokenUrl::new(token_url.to_string()).expect("Invalid token endpoint URL");
+  let redirect_url = RedirectUrl::new(format!("{}/oauth-callback/{}", config.domain, provider))
     .expect("Invalid redirect URL");
 
   BasicClient::new(client_id)
@@ -49,14 +86,28 @@ pub fn init_oauth_client(config: &crate::conf::Config) -> Client {
     .set_redirect_uri(redirect_url)
 }
 
+/// Client and settings for a provider, if it's configured.
+fn provider_client(
+  state: &AppState,
+  provider: Provider,
+) -> Option<(&Client, &crate::conf::OAuthConfig)> {
+  match provider {
+    Provider::Codeberg => Some((&state.oauth_client, &state.config.oauth)),
+    Provider::Github => Some((state.github_client.as_ref()?, state.config.github.as_ref()?)),
+  }
+}
+
 // Handlers
 
-pub async fn login_codeberg(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-  let (auth_url, _csrf_token) = state
-    .oauth_client
+pub async fn login(State(state): State<Arc<AppState>>, Path(provider): Path<Provider>) -> Response {
+  let Some((client, oauth_conf)) = provider_client(&state, provider) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let (auth_url, _csrf_token) = client
     .authorize_url(CsrfToken::new_random)
+    .add_scopes(oauth_conf.scopes.iter().cloned().map(Scope::new))
     .url();
-  Redirect::to(auth_url.as_str())
+  Redirect::to(auth_url.as_str()).into_response()
 }
 
 #[derive(serde::Deserialize)]
@@ -65,17 +116,19 @@ pub struct AuthRequest {
   state: String,
 }
 
-#[derive(serde::Deserialize)]
-struct CodebergUser {
-  id: i64,
-  username: String,
+/// Look up a configured key in the user-info JSON; `a.b` descends into objects.
+fn user_field<'a>(info: &'a serde_json::Value, key: &str) -> Option<&'a serde_json::Value> {
+  key.split('.').try_fold(info, |v, k| v.get(k))
 }
 
-pub async fn callback_codeberg(
+pub async fn callback(
   State(state): State<Arc<AppState>>,
+  Path(provider): Path<Provider>,
   cookies: Cookies,
   Query(params): Query<AuthRequest>,
 ) -> Result<Redirect, AppError> {
+  let (client, oauth_conf) = provider_client(&state, provider)
+    .ok_or_else(|| anyhow!("{} login is not enabled", provider))?;
   let code = AuthorizationCode::new(params.code);
   let _state = CsrfToken::new(params.state.clone());
 
@@ -84,21 +137,21 @@ pub async fn callback_codeberg(
     .build()
     .expect("Client should build");
 
-  let token = state
-    .oauth_client
-    .exchange_code(code)
-    .request_async(&http_client)
-    .await;
+  let token = client.exchange_code(code).request_async(&http_client).await;
 
-  if token.is_err() {
-    tracing::error!("exchange_code failed, error: {:?}", token.unwrap_err());
-    return Err(anyhow!("exchange_code failed").into());
-  }
-  let token = token.unwrap();
+  let token = match token {
+    Ok(token) => token,
+    Err(e) => {
+      tracing::error!("exchange_code failed, error: {:?}", e);
+      return Err(anyhow!("exchange_code failed").into());
+    }
+  };
 
-  let user_info: CodebergUser = serde_json::from_str(
+  let user_info: serde_json::Value = serde_json::from_str(
     &http_client
-      .get(format!("{}/user", CODEBERG_API_BASE_URL))
+      .get(&oauth_conf.user_info_url)
+      // GitHub rejects API requests without a user agent
+      .header("User-Agent", "quiz-time")
       .header(
         "Authorization",
         format!("Bearer {}", token.access_token().secret()),
@@ -109,18 +162,28 @@ pub async fn callback_codeberg(
       .await?,
   )?;
 
+  let user_id = user_field(&user_info, &oauth_conf.id_field)
+    .and_then(|v| v.as_i64().or_else(|| v.as_str()?.parse().ok()))
+    .map(|id| provider.namespaced_id(id))
+    .ok_or_else(|| anyhow!("user info has no numeric `{}`", oauth_conf.id_field))?;
+  let username = user_field(&user_info, &oauth_conf.username_field)
+    .and_then(|v| v.as_str())
+    .ok_or_else(|| anyhow!("user info has no string `{}`", oauth_conf.username_field))?
+    .to_string();
+
   // Check if user exists to handle roles
-  let user = if let Some(existing) = state.users.get_mut(&user_info.id) {
+  let user = if let Some(existing) = state.users.get_mut(&user_id) {
     existing.clone()
   } else {
     let new_user = User {
-      id: user_info.id,
-      name: user_info.username,
+      id: user_id,
+      name: username,
       password: None,
       role: Role::Normal,
       valid_after: chrono::Utc::now().timestamp(),
     };
-    state.users.insert(user_info.id, new_user.clone());
+    state.users.insert(user_id, new_user.clone());
+    state.mark_users_changed();
     new_user
   };
 
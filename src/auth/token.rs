@@ -19,6 +19,12 @@ pub struct TokenManager {
   decoding_key: DecodingKey,
 }
 
+impl Default for TokenManager {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
 impl TokenManager {
   pub fn new() -> Self {
     let mut key_bytes = [0u8; 32];
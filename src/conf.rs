@@ -1,26 +1,255 @@
+use crate::models::Timers;
+use anyhow::{Context, Result, anyhow, bail};
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
   pub domain: String,
+  /// Interface and port the server listens on
+  pub bind_addr: SocketAddr,
+  /// Codeberg, or whatever Gitea-like server the profile settings point at
   pub oauth: OAuthConfig,
+  /// Optional second provider; enabled when its client id and secret are set
+  pub github: Option<OAuthConfig>,
+  /// Base capacity of each room's broadcast channel; rooms add headroom per player slot.
+  /// Every slot keeps a message alive until all receivers pass it, so larger buffers
+  /// trade memory for fewer lagged receivers in busy rooms.
+  pub room_channel_capacity: usize,
+  /// Longest accepted problem, in grapheme clusters
+  pub max_problem_len: usize,
+  /// Longest accepted answer, in chars
+  pub max_answer_len: usize,
+  /// Answers, drafts and chat messages larger than this, in bytes, are refused
+  /// outright rather than trimmed
+  pub max_message_bytes: usize,
+  /// Rooms with nobody in them and no game running are removed after this many
+  /// seconds; 0 keeps them forever
+  pub room_idle_secs: u64,
+  /// Where finished games are saved as JSON; `None` turns saving off
+  pub results_dir: Option<String>,
+  /// Pinyin dictionary, one `char,pinyin,frequency` entry per line
+  pub dict_path: String,
+  /// Per-user totals across games; `None` keeps them only until a restart
+  pub stats_file: Option<String>,
+  /// Rooms are written here on shutdown and read back on start; `None` forgets them
+  pub rooms_file: Option<String>,
+  /// Turn lengths new rooms start with where their creator leaves them unset
+  pub default_timers: Timers,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct OAuthConfig {
   pub client_id: String,
   pub client_secret: String,
+  /// Scopes requested on the authorize redirect
+  pub scopes: Vec<String>,
+  /// Endpoint returning the logged-in user's profile as JSON
+  pub user_info_url: String,
+  /// Key of the numeric user id in the profile; dots descend into nested objects
+  pub id_field: String,
+  /// Key of the display name in the profile; dots descend into nested objects
+  pub username_field: String,
 }
 
+/// Settings as read from the config file; anything left out there falls back
+/// to the environment and then to the built-in default.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+  domain: Option<String>,
+  bind_addr: Option<IpAddr>,
+  port: Option<u16>,
+  oauth: Option<FileOAuthConfig>,
+  github: Option<FileOAuthConfig>,
+  room_channel_capacity: Option<usize>,
+  max_problem_len: Option<usize>,
+  max_answer_len: Option<usize>,
+  max_message_bytes: Option<usize>,
+  room_idle_secs: Option<u64>,
+  results_dir: Option<String>,
+  dict_path: Option<String>,
+  stats_file: Option<String>,
+  rooms_file: Option<String>,
+  default_pick_secs: Option<u64>,
+  default_answer_secs: Option<u64>,
+  default_describer_secs: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileOAuthConfig {
+  client_id: Option<String>,
+  client_secret: Option<String>,
+  scopes: Option<Vec<String>>,
+  user_info_url: Option<String>,
+  id_field: Option<String>,
+  username_field: Option<String>,
+}
+
+impl FileConfig {
+  /// Read `QUIZ_TIME_CONFIG_PATH`, or `config.toml` if that's unset. Only a
+  /// missing default file is fine; a path given explicitly must exist.
+  fn read() -> Result<Self> {
+    let (path, explicit) = match env::var("QUIZ_TIME_CONFIG_PATH") {
+      Ok(p) => (p, true),
+      Err(_) => ("config.toml".to_string(), false),
+    };
+    match std::fs::read_to_string(&path) {
+      Ok(text) => toml::from_str(&text).with_context(|| format!("invalid config file {}", path)),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => Ok(Self::default()),
+      Err(e) => Err(e).with_context(|| format!("could not read config file {}", path)),
+    }
+  }
+}
+
+/// The environment variable `name` if set, else the config file's value.
+fn setting<T: FromStr>(name: &str, file: Option<T>) -> Result<Option<T>>
+where
+  T::Err: std::fmt::Display,
+{
+  match env::var(name) {
+    Ok(v) => match v.parse() {
+      Ok(parsed) => Ok(Some(parsed)),
+      Err(e) => bail!("{} {:?} is invalid: {}", name, v, e),
+    },
+    Err(_) => Ok(file),
+  }
+}
+
+/// A path setting where an empty value turns the feature off.
+fn optional_path(name: &str, file: Option<String>, default: &str) -> Result<Option<String>> {
+  let path = setting(name, file)?.unwrap_or_else(|| default.to_string());
+  Ok((!path.is_empty()).then_some(path))
+}
+
+impl OAuthConfig {
+  /// Read `{prefix}_CLIENT_ID`, `{prefix}_CLIENT_SECRET` and the optional
+  /// `{prefix}_SCOPES`, `_USER_INFO_URL`, `_ID_FIELD` and `_USERNAME_FIELD`,
+  /// over the config file's section. `None` when no client is set up at all.
+  fn load(
+    prefix: &str,
+    file: Option<FileOAuthConfig>,
+    user_info_url: &str,
+    username_field: &str,
+  ) -> Result<Option<Self>> {
+    let file = file.unwrap_or_default();
+    let name = |n: &str| format!("{}_{}", prefix, n);
+    let client_id = setting(&name("CLIENT_ID"), file.client_id)?;
+    let client_secret = setting(&name("CLIENT_SECRET"), file.client_secret)?;
+    let (client_id, client_secret) = match (client_id, client_secret) {
+      (Some(id), Some(secret)) => (id, secret),
+      (None, None) => return Ok(None),
+      _ => bail!(
+        "{} and {} must be set together",
+        name("CLIENT_ID"),
+        name("CLIENT_SECRET")
+      ),
+    };
+    let scopes = match env::var(name("SCOPES")) {
+      Ok(v) => v
+        .split([',', ' '])
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect(),
+      Err(_) => file.scopes.unwrap_or_default(),
+    };
+    Ok(Some(Self {
+      client_id,
+      client_secret,
+      scopes,
+      user_info_url: setting(&name("USER_INFO_URL"), file.user_info_url)?
+        .unwrap_or_else(|| user_info_url.to_string()),
+      id_field: setting(&name("ID_FIELD"), file.id_field)?.unwrap_or_else(|| "id".to_string()),
+      username_field: setting(&name("USERNAME_FIELD"), file.username_field)?
+        .unwrap_or_else(|| username_field.to_string()),
+    }))
+  }
+}
+
+impl Config {
+  /// Settings from the config file, with environment variables taking
+  /// precedence over it.
+  pub fn load() -> Result<Self> {
+    let file = FileConfig::read()?;
+    let ip = setting("QUIZ_TIME_BIND_ADDR", file.bind_addr)?.unwrap_or(IpAddr::from([0, 0, 0, 0]));
+    let port = setting("QUIZ_TIME_PORT", file.port)?.unwrap_or(8080);
+    let oauth = OAuthConfig::load(
+      "QUIZ_TIME_OAUTH",
+      file.oauth,
+      &format!("{}/user", crate::auth::oauth::CODEBERG_API_BASE_URL),
+      "username",
+    )?
+    .context("QUIZ_TIME_OAUTH_CLIENT_ID and QUIZ_TIME_OAUTH_CLIENT_SECRET must be set, or client_id and client_secret under [oauth] in the config file")?;
+    let default_timers = Timers {
+      pick_secs: setting("QUIZ_TIME_DEFAULT_PICK_SECS", file.default_pick_secs)?,
+      answer_secs: setting("QUIZ_TIME_DEFAULT_ANSWER_SECS", file.default_answer_secs)?,
+      describer_secs: setting(
+        "QUIZ_TIME_DEFAULT_DESCRIBER_SECS",
+        file.default_describer_secs,
+      )?,
+    };
+    crate::game::room::validate_timers(&default_timers)
+      .map_err(|e| anyhow!("invalid default timers: {}", e))?;
+    Ok(Self {
+      domain: setting("QUIZ_TIME_DOMAIN", file.domain)?
+        .unwrap_or_else(|| "http://127.0.0.1:8080".to_string()),
+      bind_addr: SocketAddr::new(ip, port),
+      oauth,
+      github: OAuthConfig::load(
+        "QUIZ_TIME_GITHUB",
+        file.github,
+        crate::auth::oauth::GITHUB_USER_INFO_URL,
+        "login",
+      )?,
+      room_channel_capacity: setting(
+        "QUIZ_TIME_ROOM_CHANNEL_CAPACITY",
+        file.room_channel_capacity,
+      )?
+      .unwrap_or(100),
+      max_problem_len: setting("QUIZ_TIME_MAX_PROBLEM_LEN", file.max_problem_len)?.unwrap_or(500),
+      max_answer_len: setting("QUIZ_TIME_MAX_ANSWER_LEN", file.max_answer_len)?.unwrap_or(128),
+      max_message_bytes: setting("QUIZ_TIME_MAX_MESSAGE_BYTES", file.max_message_bytes)?
+        .unwrap_or(1024),
+      room_idle_secs: setting("QUIZ_TIME_ROOM_IDLE_SECS", file.room_idle_secs)?.unwrap_or(600),
+      results_dir: optional_path("QUIZ_TIME_RESULTS_DIR", file.results_dir, "results")?,
+      dict_path: setting("QUIZ_TIME_DICT_PATH", file.dict_path)?
+        .unwrap_or_else(|| "dict.txt".to_string()),
+      stats_file: optional_path("QUIZ_TIME_STATS_FILE", file.stats_file, "stats.json")?,
+      rooms_file: optional_path("QUIZ_TIME_ROOMS_FILE", file.rooms_file, "rooms.json")?,
+      default_timers,
+    })
+  }
+}
+
+#[cfg(test)]
 impl Config {
-  pub fn load() -> Self {
+  /// Built-in defaults, with nothing read from the environment or written to disk.
+  pub fn for_tests() -> Self {
     Self {
-      domain: env::var("QUIZ_TIME_DOMAIN").unwrap_or_else(|_| "http://127.0.0.1:8080".to_string()),
+      domain: "http://127.0.0.1:8080".to_string(),
+      bind_addr: SocketAddr::new(IpAddr::from([127, 0, 0, 1]), 0),
       oauth: OAuthConfig {
-        client_id: env::var("QUIZ_TIME_OAUTH_CLIENT_ID").unwrap(),
-        client_secret: env::var("QUIZ_TIME_OAUTH_CLIENT_SECRET").unwrap(),
+        client_id: "id".to_string(),
+        client_secret: "secret".to_string(),
+        scopes: vec![],
+        user_info_url: String::new(),
+        id_field: "id".to_string(),
+        username_field: "username".to_string(),
       },
+      github: None,
+      room_channel_capacity: 100,
+      max_problem_len: 500,
+      max_answer_len: 128,
+      max_message_bytes: 1024,
+      room_idle_secs: 0,
+      results_dir: None,
+      dict_path: "dict.txt".to_string(),
+      stats_file: None,
+      rooms_file: None,
+      default_timers: Timers::default(),
     }
   }
 }
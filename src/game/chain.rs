@@ -1,42 +1,150 @@
+use super::hint::StagedHint;
 use crate::models::*;
 use chrono::Local;
-use rand::seq::SliceRandom;
-use std::collections::HashMap;
+use rand::seq::{IteratorRandom, SliceRandom};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
+use unicode_normalization::UnicodeNormalization;
 
+pub const DEFAULT_MIN_TAKE_INTERVAL: Duration = Duration::from_millis(150);
+pub const DEFAULT_PICK_TIME: Duration = Duration::from_secs(3);
+pub const DEFAULT_ANSWER_TIME: Duration = Duration::from_secs(60);
+
+/// Points for a fully correct answer, shared out evenly between the blanks
+const BASE_POINTS: u64 = 100;
+/// Extra points for a fully correct answer, falling from this when the answer
+/// window opens to nothing when it closes; early answers get all of it
+const SPEED_BONUS: u64 = 50;
+/// Taken off the speed bonus for each character revealed as a hint before the
+/// answer went in, down to no bonus at all
+const HINT_PENALTY: u64 = 10;
+
+#[derive(Serialize, Deserialize)]
 pub struct ChainGame {
   pub problem_text: Vec<char>,
   pub answer_text: String,
+  /// Also judged correct for single answers; `answer_text` stays the one shown
+  pub alt_answers: Vec<String>,
   pub hint_text: String,
+  pub hint_stages: StagedHint,
+  /// Blanks checked independently; a single answer leaves this empty
+  pub answer_parts: Vec<String>,
+  pub match_strategy: MatchStrategy,
+  /// Replaces `match_strategy` for single answers; anchored at both ends
+  #[serde(with = "super::snapshot::option_regex")]
+  pub answer_regex: Option<Regex>,
   pub phase: GamePhase,
   pub players: Vec<i64>,
+  /// Shuffle the seating on start; off when the order was decided beforehand
+  pub shuffle_order: bool,
   pub player_data: HashMap<i64, ChainPlayerState>,
   pub cursor: usize,
   pub current_turn_idx: usize,
+  /// Picking turns started so far, counting the current one
+  pub turn_no: usize,
+  #[serde(with = "super::snapshot::option_instant")]
   pub turn_deadline: Option<Instant>,
+  #[serde(with = "super::snapshot::option_instant")]
   pub answer_deadline: Option<Instant>,
+  /// When the answer window opened, or the game ended if it never did
+  #[serde(with = "super::snapshot::option_instant")]
+  pub answer_opened_at: Option<Instant>,
+  /// While answering, reveal a random hidden character to everyone at this cadence
+  pub answer_hint_interval: Option<Duration>,
+  #[serde(with = "super::snapshot::option_instant")]
+  pub next_hint_at: Option<Instant>,
+  pub revealed_indices: HashSet<usize>,
+  /// Owners can't see their own characters until settlement
+  pub blind: bool,
+  /// Manual takes arriving faster than this after the previous one are ignored
+  pub min_take_interval: Duration,
+  /// Until answering starts, the grid only covers claimed cells
+  pub hide_length: bool,
+  /// Ask the last picker before handing them the rest
+  pub last_pick_choice: bool,
+  /// The current turn is that last picker's choice
+  pub final_pick: bool,
+  /// Turn deadline from before the picker's latest manual take, while it can be undone
+  #[serde(with = "super::snapshot::option_instant")]
+  pub undo_deadline: Option<Instant>,
+  /// The current picker already took back a take this turn
+  pub undo_used: bool,
+  pub pick_time: Duration,
+  /// Countdown between the start and the first turn, so nobody gets a head start
+  pub start_delay: Duration,
+  /// Set during that countdown; the first turn's clock arms when it passes
+  #[serde(with = "super::snapshot::option_instant")]
+  pub starts_at: Option<Instant>,
+  pub answer_time: Duration,
+  /// Privately notify each player when their picking turn starts
+  pub turn_toast: bool,
+  /// Set by an admin at settlement to show every cell and answer to everyone
+  pub force_reveal: bool,
+  /// Game clock, moved along with the timers on pause
+  #[serde(with = "super::snapshot::option_instant")]
+  pub started_at: Option<Instant>,
+  /// How long the game ran, set at settlement
+  pub duration_ms: Option<u64>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ChainPlayerState {
   pub status: PlayerStatus,
   pub obtained_indices: Vec<usize>,
+  /// `obtained_indices` with the turn each cell was taken on
+  pub takes: Vec<TakeRecord>,
   pub answer: Option<String>,
+  #[serde(with = "super::snapshot::option_instant")]
+  pub submitted_at: Option<Instant>,
+  /// Characters revealed as hints by the time the answer went in
+  pub hints_seen: usize,
+  #[serde(with = "super::snapshot::option_instant")]
+  pub last_take: Option<Instant>,
+  pub draft: Option<String>,
 }
 
 impl ChainGame {
   pub fn new(prob: String, ans: String, hint: String) -> Self {
     Self {
-      problem_text: prob.chars().collect(),
+      problem_text: prob.chars().filter(|c| *c != '\r').collect(),
       answer_text: ans,
+      alt_answers: vec![],
+      answer_parts: vec![],
+      match_strategy: MatchStrategy::default(),
+      answer_regex: None,
       hint_text: hint,
+      hint_stages: StagedHint::default(),
       phase: GamePhase::Waiting,
       players: vec![],
+      shuffle_order: true,
       player_data: HashMap::new(),
       cursor: 0,
       current_turn_idx: 0,
+      turn_no: 0,
       turn_deadline: None,
       answer_deadline: None,
+      answer_opened_at: None,
+      answer_hint_interval: None,
+      next_hint_at: None,
+      revealed_indices: HashSet::new(),
+      blind: false,
+      min_take_interval: DEFAULT_MIN_TAKE_INTERVAL,
+      hide_length: false,
+      last_pick_choice: false,
+      final_pick: false,
+      undo_deadline: None,
+      undo_used: false,
+      pick_time: DEFAULT_PICK_TIME,
+      start_delay: Duration::ZERO,
+      starts_at: None,
+      started_at: None,
+      duration_ms: None,
+      answer_time: DEFAULT_ANSWER_TIME,
+      turn_toast: true,
+      force_reveal: false,
     }
   }
 
@@ -48,7 +156,12 @@ impl ChainGame {
         ChainPlayerState {
           status: PlayerStatus::Waiting,
           obtained_indices: vec![],
+          takes: vec![],
           answer: None,
+          submitted_at: None,
+          hints_seen: 0,
+          last_take: None,
+          draft: None,
         },
       );
     }
@@ -58,33 +171,82 @@ impl ChainGame {
     if self.players.is_empty() {
       return;
     }
-    self.players.shuffle(&mut rand::thread_rng());
-    self.phase = GamePhase::Picking;
+    if self.shuffle_order {
+      self.players.shuffle(&mut rand::thread_rng());
+    }
+    self.set_phase(tx, GamePhase::Picking);
     self.cursor = 0;
     self.current_turn_idx = 0;
-    if let Some(first) = self.players.first() {
-      if let Some(p) = self.player_data.get_mut(first) {
-        p.status = PlayerStatus::Picking;
-      }
+    self.turn_no = 1;
+    self.started_at = Some(Instant::now());
+    if let Some(&first) = self.players.first()
+      && let Some(p) = self.player_data.get_mut(&first)
+    {
+      p.status = PlayerStatus::Picking;
     }
-    self.turn_deadline = Some(Instant::now() + Duration::from_secs(3));
+    let text = if self.start_delay.is_zero() {
+      self.arm_first_turn(tx);
+      "Chain game started".to_string()
+    } else {
+      self.starts_at = Some(Instant::now() + self.start_delay);
+      format!("Chain game starting in {}s", self.start_delay.as_secs())
+    };
     let _ = tx.send(InternalMsg::Log {
       who: "System".into(),
-      text: "Chain game started".into(),
+      text,
       time: Local::now().format("%H:%M:%S").to_string(),
     });
   }
 
+  fn arm_first_turn(&mut self, tx: &broadcast::Sender<InternalMsg>) {
+    self.starts_at = None;
+    self.hint_stages.start();
+    self.turn_deadline = Some(Instant::now() + self.pick_time);
+    if let Some(&first) = self.players.get(self.current_turn_idx) {
+      self.notify_turn(tx, first);
+    }
+  }
+
   pub fn handle_join(&mut self, _: i64, _: &broadcast::Sender<InternalMsg>) {}
   pub fn handle_leave(&mut self, _: i64, _: &broadcast::Sender<InternalMsg>) {}
 
   pub fn handle_action(&mut self, pid: i64, action: String, tx: &broadcast::Sender<InternalMsg>) {
-    if self.phase == GamePhase::Picking && self.players.get(self.current_turn_idx) == Some(&pid) {
-      if action == "take" {
+    if self.can_act(pid) {
+      if self.final_pick {
+        match action.as_str() {
+          "take" => self.take_rest(pid, tx),
+          "stop" => {
+            if let Some(p) = self.player_data.get_mut(&pid) {
+              p.status = PlayerStatus::Stopped;
+            }
+            self.send_log(tx, "Action", format!("{} declined the rest", pid));
+            self.advance_turn(tx);
+          }
+          _ => {}
+        }
+      } else if action == "take" {
+        // Ignore scripted bursts; the auto-take on timeout is not throttled
+        let now = Instant::now();
+        if let Some(p) = self.player_data.get_mut(&pid) {
+          if p
+            .last_take
+            .is_some_and(|t| now.duration_since(t) < self.min_take_interval)
+          {
+            return;
+          }
+          p.last_take = Some(now);
+        }
+        let before = self.turn_deadline;
+        let taken = self.player_data.get(&pid).map(|p| p.takes.len());
         self.perform_take(tx);
+        if !self.undo_used && self.player_data.get(&pid).map(|p| p.takes.len()) > taken {
+          self.undo_deadline = before;
+        }
+      } else if action == "undo" {
+        self.undo_take(pid, tx);
       } else if action == "stop" {
         if let Some(p) = self.player_data.get_mut(&pid) {
-          p.status = PlayerStatus::Answering;
+          p.status = PlayerStatus::Stopped;
         }
         self.send_log(tx, "Action", format!("{} stopped", pid));
         self.advance_turn(tx);
@@ -92,22 +254,69 @@ impl ChainGame {
     }
   }
 
-  pub fn handle_answer(&mut self, pid: i64, content: String, tx: &broadcast::Sender<InternalMsg>) {
-    let can_answer = if let Some(p) = self.player_data.get(&pid) {
-      p.status == PlayerStatus::Answering
-        || (self.phase == GamePhase::Answering && p.status != PlayerStatus::Submitted)
-    } else {
-      false
+  /// Give back the picker's latest take and the time it cost them; once per turn.
+  fn undo_take(&mut self, pid: i64, tx: &broadcast::Sender<InternalMsg>) {
+    let Some(deadline) = self.undo_deadline.take() else {
+      return;
+    };
+    let Some(p) = self.player_data.get_mut(&pid) else {
+      return;
+    };
+    let Some(index) = p.obtained_indices.pop() else {
+      return;
     };
+    p.takes.pop();
+    self.cursor = index;
+    self.turn_deadline = Some(deadline);
+    self.undo_used = true;
+    self.send_log(tx, "Action", format!("{} undid a take", pid));
+    let _ = tx.send(InternalMsg::StateUpdated);
+  }
 
-    if can_answer {
-      if let Some(p) = self.player_data.get_mut(&pid) {
-        p.answer = Some(content);
-        p.status = PlayerStatus::Submitted;
-        self.send_log(tx, "System", format!("{} submitted answer", pid));
-        self.check_all_submitted(tx);
-        let _ = tx.send(InternalMsg::StateUpdated);
-      }
+  fn can_undo(&self, pid: i64) -> bool {
+    self.can_act(pid) && !self.final_pick && self.undo_deadline.is_some()
+  }
+
+  fn can_act(&self, pid: i64) -> bool {
+    self.phase == GamePhase::Picking
+      && self.starts_at.is_none()
+      && self.players.get(self.current_turn_idx) == Some(&pid)
+  }
+
+  fn can_answer(&self, pid: i64) -> bool {
+    self.player_data.get(&pid).is_some_and(|p| {
+      matches!(p.status, PlayerStatus::Answering | PlayerStatus::Stopped)
+        || (self.phase == GamePhase::Answering && p.status != PlayerStatus::Submitted)
+    })
+  }
+
+  pub fn has_submitted(&self, pid: i64) -> bool {
+    self
+      .player_data
+      .get(&pid)
+      .is_some_and(|p| p.answer.is_some())
+  }
+
+  pub fn handle_answer(&mut self, pid: i64, content: String, tx: &broadcast::Sender<InternalMsg>) {
+    if self.can_answer(pid)
+      && let Some(p) = self.player_data.get_mut(&pid)
+    {
+      p.answer = Some(content);
+      p.status = PlayerStatus::Submitted;
+      p.submitted_at = Some(Instant::now());
+      p.hints_seen = self.revealed_indices.len();
+      self.send_log(tx, "System", format!("{} submitted answer", pid));
+      self.check_all_submitted(tx);
+      let _ = tx.send(InternalMsg::StateUpdated);
+    }
+  }
+
+  /// Remember what the player has typed so far; nobody else ever sees it.
+  pub fn handle_draft(&mut self, pid: i64, content: String) {
+    if self.can_answer(pid)
+      && let Some(p) = self.player_data.get_mut(&pid)
+    {
+      p.draft = Some(content);
     }
   }
 
@@ -115,77 +324,158 @@ impl ChainGame {
     &mut self,
     tx: &broadcast::Sender<InternalMsg>,
     room_players: &HashMap<i64, super::room::RoomPlayer>,
+    grace: Duration,
   ) {
     let now = Instant::now();
 
+    // Nothing moves until the countdown is over
+    if let Some(t) = self.starts_at {
+      if now < t {
+        return;
+      }
+      self.arm_first_turn(tx);
+      let _ = tx.send(InternalMsg::StateUpdated);
+    }
+
+    if self.phase != GamePhase::Settlement
+      && let Some(stage) = self.hint_stages.tick(now)
+    {
+      self.send_log(tx, "System", format!("New hint: {}", stage));
+      let _ = tx.send(InternalMsg::StateUpdated);
+    }
+
     let player_ids = self.players.clone();
 
     for pid in player_ids {
-      let is_online = room_players
+      let (is_online, offline_for, resuming) = room_players
         .get(&pid)
-        .map(|rp| rp.is_online)
-        .unwrap_or(false);
+        .map(|rp| {
+          let resuming = rp.resume_until.is_some_and(|t| now < t);
+          (rp.is_online, rp.last_seen.elapsed(), resuming)
+        })
+        .unwrap_or((false, Duration::MAX, false));
 
       if !is_online {
-        // Drop handler logic for picking phase
-        if self.phase == GamePhase::Picking && self.players.get(self.current_turn_idx) == Some(&pid)
+        // Past the grace period a disconnected player forfeits their answer
+        if self.phase == GamePhase::Answering
+          && offline_for > grace
+          && let Some(p) = self.player_data.get_mut(&pid)
+          && p.status != PlayerStatus::Submitted
+        {
+          p.status = PlayerStatus::Submitted;
+          self.send_log(tx, "System", format!("{} forfeited (disconnected)", pid));
+          let _ = tx.send(InternalMsg::StateUpdated);
+        }
+        // Drop handler logic for picking phase. A picker who may still resume,
+        // as after a refresh, keeps the turn until the window lapses
+        if self.phase == GamePhase::Picking
+          && !resuming
+          && self.players.get(self.current_turn_idx) == Some(&pid)
         {
           if let Some(p) = self.player_data.get_mut(&pid) {
-            // Change: Offline/Timeout user moves to answering (effectively skipped)
-            p.status = PlayerStatus::Answering;
+            // Offline user stops picking (effectively skipped)
+            p.status = PlayerStatus::Stopped;
           }
           self.advance_turn(tx);
         }
       }
     }
 
-    if self.phase == GamePhase::Picking {
-      if let Some(d) = self.turn_deadline {
-        if now > d {
-          self.perform_take(tx);
-        }
+    if self.phase == GamePhase::Picking
+      && let Some(d) = self.turn_deadline
+      && now > d
+    {
+      if self.final_pick {
+        // Undecided: fall back to handing over everything
+        self.take_rest(self.players[self.current_turn_idx], tx);
+      } else {
+        // Takes made on timeout can't be undone, and neither can anything before them
+        self.undo_deadline = None;
+        self.perform_take(tx);
       }
     }
     if self.phase == GamePhase::Answering {
-      if let Some(d) = self.answer_deadline {
-        if now > d {
-          self.finish_game(tx);
-        }
+      if let Some(d) = self.answer_deadline
+        && now > d
+      {
+        self.finish_game(tx);
+      }
+      if let Some(at) = self.next_hint_at
+        && now > at
+      {
+        self.reveal_hint(tx);
       }
       self.check_all_submitted(tx);
     }
   }
 
+  /// Make one random, not yet revealed character visible to every viewer.
+  fn reveal_hint(&mut self, tx: &broadcast::Sender<InternalMsg>) {
+    let hidden: Vec<usize> = (0..self.problem_text.len())
+      .filter(|i| !self.is_break(*i) && !self.revealed_indices.contains(i))
+      .collect();
+    let Some(&idx) = hidden.iter().choose(&mut rand::thread_rng()) else {
+      self.next_hint_at = None;
+      return;
+    };
+    self.revealed_indices.insert(idx);
+    self.next_hint_at = if hidden.len() > 1 {
+      self.answer_hint_interval.map(|d| Instant::now() + d)
+    } else {
+      None
+    };
+    self.send_log(tx, "System", "Hint: one more character revealed".into());
+    let _ = tx.send(InternalMsg::StateUpdated);
+  }
+
+  /// Newlines lay out the grid in rows; they are never claimable.
+  fn is_break(&self, idx: usize) -> bool {
+    self.problem_text[idx] == '\n'
+  }
+
   fn perform_take(&mut self, tx: &broadcast::Sender<InternalMsg>) {
-    let curr_pid = self.players[self.current_turn_idx].clone();
+    let curr_pid = self.players[self.current_turn_idx];
+    while self.cursor < self.problem_text.len() && self.is_break(self.cursor) {
+      self.cursor += 1;
+    }
     if self.cursor >= self.problem_text.len() {
       if let Some(p) = self.player_data.get_mut(&curr_pid) {
-        // Change: Run out of chars -> Answering
-        p.status = PlayerStatus::Answering;
+        // Run out of chars -> Stopped
+        p.status = PlayerStatus::Stopped;
       }
       self.advance_turn(tx);
       return;
     }
     if let Some(p) = self.player_data.get_mut(&curr_pid) {
       p.obtained_indices.push(self.cursor);
+      p.takes.push(TakeRecord {
+        index: self.cursor,
+        turn: self.turn_no,
+      });
     }
     self.cursor += 1;
-    self.turn_deadline = Some(Instant::now() + Duration::from_secs(3));
+    self.turn_deadline = Some(Instant::now() + self.pick_time);
     let _ = tx.send(InternalMsg::StateUpdated);
   }
 
   fn advance_turn(&mut self, tx: &broadcast::Sender<InternalMsg>) {
+    if let Some(stage) = self.hint_stages.next_turn() {
+      self.send_log(tx, "System", format!("New hint: {}", stage));
+    }
+    self.final_pick = false;
+    self.undo_deadline = None;
+    self.undo_used = false;
     let mut next_idx = (self.current_turn_idx + 1) % self.players.len();
     let mut found = false;
 
-    // Look for next waiting player
+    // Look for next waiting player; Stopped and Submitted players are done picking
     for _ in 0..self.players.len() {
       let pid = &self.players[next_idx];
-      if let Some(p) = self.player_data.get(pid) {
-        if p.status == PlayerStatus::Waiting {
-          found = true;
-          break;
-        }
+      if let Some(p) = self.player_data.get(pid)
+        && p.status == PlayerStatus::Waiting
+      {
+        found = true;
+        break;
       }
       next_idx = (next_idx + 1) % self.players.len();
     }
@@ -200,48 +490,75 @@ impl ChainGame {
     if !found {
       // Everyone has had a turn
       self.enter_answering(tx);
+    } else if waiting_count == 1 && self.last_pick_choice {
+      // Last person decides whether to take the rest
+      self.current_turn_idx = next_idx;
+      self.turn_no += 1;
+      self.final_pick = true;
+      let next_pid = self.players[next_idx];
+      if let Some(p) = self.player_data.get_mut(&next_pid) {
+        p.status = PlayerStatus::Picking;
+      }
+      self.notify_turn(tx, next_pid);
+      self.turn_deadline = Some(Instant::now() + self.pick_time);
+      let _ = tx.send(InternalMsg::StateUpdated);
     } else if waiting_count == 1 {
       // Last person takes all remaining
-      let last_pid = self.players[next_idx].clone();
-      let remaining = self.problem_text.len() - self.cursor;
-      if remaining > 0 {
-        if let Some(p) = self.player_data.get_mut(&last_pid) {
-          for i in 0..remaining {
-            p.obtained_indices.push(self.cursor + i);
-          }
-        }
-        self.cursor += remaining;
-      }
-      if let Some(p) = self.player_data.get_mut(&last_pid) {
-        // Change: Last person finished -> Answering
-        p.status = PlayerStatus::Answering;
-      }
-      self.enter_answering(tx);
+      self.turn_no += 1;
+      self.take_rest(self.players[next_idx], tx);
     } else {
       // Normal turn passing
       self.current_turn_idx = next_idx;
-      let next_pid = self.players[next_idx].clone();
+      self.turn_no += 1;
+      let next_pid = self.players[next_idx];
       if let Some(p) = self.player_data.get_mut(&next_pid) {
         p.status = PlayerStatus::Picking;
       }
-      self.turn_deadline = Some(Instant::now() + Duration::from_secs(3));
+      self.notify_turn(tx, next_pid);
+      self.turn_deadline = Some(Instant::now() + self.pick_time);
       let _ = tx.send(InternalMsg::StateUpdated);
     }
   }
 
+  /// Give every unclaimed character to the last picker and start answering.
+  fn take_rest(&mut self, last_pid: i64, tx: &broadcast::Sender<InternalMsg>) {
+    let remaining: Vec<usize> = (self.cursor..self.problem_text.len())
+      .filter(|i| !self.is_break(*i))
+      .collect();
+    let turn = self.turn_no;
+    if let Some(p) = self.player_data.get_mut(&last_pid) {
+      p.takes
+        .extend(remaining.iter().map(|&index| TakeRecord { index, turn }));
+      p.obtained_indices.extend(remaining);
+    }
+    self.cursor = self.problem_text.len();
+    if let Some(p) = self.player_data.get_mut(&last_pid) {
+      // Change: Last person finished -> Answering
+      p.status = PlayerStatus::Answering;
+    }
+    self.enter_answering(tx);
+  }
+
   fn enter_answering(&mut self, tx: &broadcast::Sender<InternalMsg>) {
-    self.phase = GamePhase::Answering;
+    self.final_pick = false;
+    self.undo_deadline = None;
+    self.set_phase(tx, GamePhase::Answering);
     self.turn_deadline = None;
-    self.answer_deadline = Some(Instant::now() + Duration::from_secs(60));
+    self.answer_deadline = Some(Instant::now() + self.answer_time);
+    self.answer_opened_at = Some(Instant::now());
+    self.next_hint_at = self.answer_hint_interval.map(|d| Instant::now() + d);
 
-    // Transition anyone who isn't already submitted/answering (e.g. Stopped, though logic above handles that)
-    // to Answering state.
+    // Everyone who hasn't submitted (Waiting or Stopped) now answers.
     for p in self.player_data.values_mut() {
       if p.status != PlayerStatus::Submitted {
         p.status = PlayerStatus::Answering;
       }
     }
-    self.send_log(tx, "System", "Picking ended. 60s to answer".into());
+    self.send_log(
+      tx,
+      "System",
+      format!("Picking ended. {}s to answer", self.answer_time.as_secs()),
+    );
     // Also check immediately, in case everyone already submitted early
     self.check_all_submitted(tx);
     let _ = tx.send(InternalMsg::StateUpdated);
@@ -267,13 +584,180 @@ impl ChainGame {
     if self.phase == GamePhase::Settlement {
       return;
     }
-    self.phase = GamePhase::Settlement;
+    self.set_phase(tx, GamePhase::Settlement);
+    self.answer_opened_at.get_or_insert_with(Instant::now);
+    self.duration_ms = self.started_at.map(|t| t.elapsed().as_millis() as u64);
     self.turn_deadline = None;
     self.answer_deadline = None;
+    self.next_hint_at = None;
+    self.hint_stages.stop();
     self.send_log(tx, "System", "Game Finished".into());
     let _ = tx.send(InternalMsg::StateUpdated);
   }
 
+  /// Push every running timer back by `by`, after a pause.
+  pub fn shift_deadlines(&mut self, by: Duration) {
+    for t in [
+      &mut self.turn_deadline,
+      &mut self.starts_at,
+      &mut self.started_at,
+      &mut self.undo_deadline,
+      &mut self.answer_deadline,
+      &mut self.next_hint_at,
+    ]
+    .into_iter()
+    .flatten()
+    {
+      *t += by;
+    }
+    self.hint_stages.shift(by);
+  }
+
+  /// End answering now; anyone who hasn't submitted hands in their draft, if any.
+  pub fn force_finish(&mut self, tx: &broadcast::Sender<InternalMsg>) -> Result<(), String> {
+    if self.phase != GamePhase::Answering {
+      return Err("Answering has not started".to_string());
+    }
+    let now = Instant::now();
+    for pid in &self.players {
+      if let Some(p) = self.player_data.get_mut(pid)
+        && p.status != PlayerStatus::Submitted
+      {
+        p.answer = Some(p.draft.take().unwrap_or_default());
+        p.status = PlayerStatus::Submitted;
+        p.submitted_at = Some(now);
+        p.hints_seen = self.revealed_indices.len();
+      }
+    }
+    self.finish_game(tx);
+    Ok(())
+  }
+
+  /// Milliseconds into the answer window the player submitted; negative if they
+  /// answered early while picking was still going.
+  fn submit_offset_ms(&self, p: &ChainPlayerState) -> Option<i64> {
+    let at = p.submitted_at?;
+    let opened = self.answer_opened_at?;
+    Some(if at >= opened {
+      (at - opened).as_millis() as i64
+    } else {
+      -((opened - at).as_millis() as i64)
+    })
+  }
+
+  /// Players whose answer is right in every blank.
+  pub fn winners(&self) -> Vec<i64> {
+    self
+      .players
+      .iter()
+      .filter(|pid| {
+        self.player_data.get(pid).is_some_and(|p| {
+          p.answer
+            .as_deref()
+            .is_some_and(|a| self.check_parts(a).iter().all(|ok| *ok))
+        })
+      })
+      .copied()
+      .collect()
+  }
+
+  /// Points earned by `pid`'s answer; nothing without one. Hints only cost
+  /// a fully correct answer, and only its speed bonus.
+  pub fn points(&self, pid: i64) -> u64 {
+    let Some(p) = self.player_data.get(&pid) else {
+      return 0;
+    };
+    let Some(answer) = p.answer.as_deref() else {
+      return 0;
+    };
+    let parts = self.check_parts(answer);
+    let right = parts.iter().filter(|ok| **ok).count() as u64;
+    let mut points = BASE_POINTS * right / parts.len() as u64;
+    if right == parts.len() as u64 {
+      let window = (self.answer_time.as_millis() as u64).max(1);
+      let used = self.submit_offset_ms(p).unwrap_or(0).max(0) as u64;
+      let bonus = SPEED_BONUS * window.saturating_sub(used) / window;
+      points += bonus.saturating_sub(HINT_PENALTY * p.hints_seen as u64);
+    }
+    points
+  }
+
+  /// Everyone seated, best score first. Ties share a rank, and the next rank
+  /// skips past them.
+  pub fn leaderboard(&self, names: &HashMap<i64, super::room::RoomPlayer>) -> Vec<ScoreEntry> {
+    let mut entries: Vec<ScoreEntry> = self
+      .players
+      .iter()
+      .map(|pid| ScoreEntry {
+        id: *pid,
+        name: names.get(pid).map(|r| r.name.clone()).unwrap_or_default(),
+        points: self.points(*pid),
+        rank: 0,
+        submitted: self.has_submitted(*pid),
+        answer_ms: self
+          .player_data
+          .get(pid)
+          .and_then(|p| self.submit_offset_ms(p)),
+      })
+      .collect();
+    entries.sort_by(|a, b| b.points.cmp(&a.points).then_with(|| a.name.cmp(&b.name)));
+    for i in 0..entries.len() {
+      entries[i].rank = if i > 0 && entries[i].points == entries[i - 1].points {
+        entries[i - 1].rank
+      } else {
+        i + 1
+      };
+    }
+    entries
+  }
+
+  /// Check a newline-separated submission blank by blank; a single-answer
+  /// game yields one entry for the whole answer.
+  fn check_parts(&self, answer: &str) -> Vec<bool> {
+    if self.answer_parts.is_empty() {
+      return vec![match &self.answer_regex {
+        Some(re) => re.is_match(answer),
+        None => std::iter::once(&self.answer_text)
+          .chain(&self.alt_answers)
+          .any(|expected| self.is_correct(answer, expected)),
+      }];
+    }
+    let mut given = answer.split('\n');
+    self
+      .answer_parts
+      .iter()
+      .map(|expected| given.next().is_some_and(|g| self.is_correct(g, expected)))
+      .collect()
+  }
+
+  fn is_correct(&self, given: &str, expected: &str) -> bool {
+    match self.match_strategy {
+      MatchStrategy::Exact => given == expected,
+      MatchStrategy::Normalized => normalize_answer(given) == normalize_answer(expected),
+      MatchStrategy::Fuzzy { max_distance } => {
+        edit_distance(&normalize_answer(given), &normalize_answer(expected)) <= max_distance
+      }
+    }
+  }
+
+  fn notify_turn(&self, tx: &broadcast::Sender<InternalMsg>, pid: i64) {
+    if self.turn_toast {
+      let _ = tx.send(InternalMsg::Toast {
+        to_user: pid,
+        msg: "Your turn to pick!".into(),
+        kind: "turn".into(),
+      });
+    }
+  }
+
+  fn set_phase(&mut self, tx: &broadcast::Sender<InternalMsg>, to: GamePhase) {
+    let _ = tx.send(InternalMsg::Phase {
+      from: self.phase.to_string(),
+      to: to.to_string(),
+    });
+    self.phase = to;
+  }
+
   fn send_log(&self, tx: &broadcast::Sender<InternalMsg>, who: &str, text: String) {
     let _ = tx.send(InternalMsg::Log {
       who: who.to_string(),
@@ -287,17 +771,9 @@ impl ChainGame {
     user_id: Option<i64>,
     show_all: bool,
     hue_map: &HashMap<i64, u16>,
-  ) -> (
-    GamePhase,
-    String,
-    Option<Instant>,
-    Option<Vec<GridCell>>,
-    Option<PinyinSpecificView>,
-    Option<bool>,
-    Option<String>,
-  ) {
+  ) -> GameViewData {
     let is_settled = self.phase == GamePhase::Settlement;
-    let can_see_all = show_all || is_settled;
+    let can_see_all = show_all || is_settled || self.force_reveal;
 
     let mut grid = Vec::new();
     // Build index ownership map
@@ -308,24 +784,42 @@ impl ChainGame {
       }
     }
 
-    for i in 0..self.problem_text.len() {
+    // Claimed cells are always a prefix, so the cursor hides nothing it shouldn't
+    let visible_len = if self.hide_length && !can_see_all && self.phase == GamePhase::Picking {
+      self.cursor
+    } else {
+      self.problem_text.len()
+    };
+
+    // The cursor only moves forward, so cells are taken in reading order and
+    // counting claimed cells gives the take order; a take-the-rest stays one block
+    let mut seq = 0;
+    for i in 0..visible_len {
       let owner_id = idx_owner.get(&i);
-      let show_char = can_see_all || (user_id.is_some() && owner_id == user_id.as_ref());
+      if owner_id.is_some() {
+        seq += 1;
+      }
+      let show_char = can_see_all
+        || self.revealed_indices.contains(&i)
+        || (!self.blind && user_id.is_some() && owner_id == user_id.as_ref());
 
       let hue = owner_id.and_then(|id| hue_map.get(id)).cloned();
 
+      let is_break = self.is_break(i);
       grid.push(GridCell {
+        is_break,
         owner_color_hue: hue,
-        char_content: if show_char {
+        char_content: if show_char && !is_break {
           Some(self.problem_text[i])
         } else {
           None
         },
+        take_seq: (is_settled && owner_id.is_some()).then_some(seq),
       });
     }
 
     let deadline = if self.phase == GamePhase::Picking {
-      self.turn_deadline
+      self.starts_at.or(self.turn_deadline)
     } else {
       self.answer_deadline
     };
@@ -335,15 +829,156 @@ impl ChainGame {
       None
     };
 
-    (
-      self.phase,
-      self.hint_text.clone(),
+    GameViewData {
+      phase: self.phase,
+      hint: self.hint_stages.text(&self.hint_text, can_see_all),
       deadline,
-      Some(grid),
-      None,
-      None,
-      correct_ans,
-    )
+      grid: Some(grid),
+      pinyin_state: None,
+      winner: None,
+      correct_answer: correct_ans,
+      my_draft: user_id
+        .and_then(|u| self.player_data.get(&u))
+        .filter(|p| p.status != PlayerStatus::Submitted)
+        .and_then(|p| p.draft.clone()),
+      answer_parts: (self.answer_parts.len() > 1).then_some(self.answer_parts.len()),
+      part_results: can_see_all.then(|| {
+        self
+          .player_data
+          .iter()
+          .map(|(pid, p)| (*pid, self.check_parts(p.answer.as_deref().unwrap_or(""))))
+          .collect()
+      }),
+      // A pattern overrides the strategy, and the shown answer is the pattern itself
+      match_strategy: (can_see_all && self.answer_regex.is_none()).then_some(self.match_strategy),
+      final_pick: self.final_pick,
+      can_undo: user_id.is_some_and(|u| self.can_undo(u)),
+      starting: self.starts_at.is_some(),
+      game_duration_ms: self.duration_ms,
+      remaining_chars: (self.phase == GamePhase::Picking && (!self.hide_length || can_see_all))
+        .then(|| {
+          (self.cursor..self.problem_text.len())
+            .filter(|i| !self.is_break(*i))
+            .count()
+        }),
+      can_act: user_id.is_some_and(|u| self.can_act(u)),
+      can_answer: user_id.is_some_and(|u| self.can_answer(u)),
+      pending_submissions: matches!(self.phase, GamePhase::Picking | GamePhase::Answering).then(
+        || {
+          self
+            .player_data
+            .values()
+            .filter(|p| p.status != PlayerStatus::Submitted)
+            .count()
+        },
+      ),
+      submit_ms: can_see_all.then(|| {
+        self
+          .player_data
+          .iter()
+          .filter_map(|(pid, p)| Some((*pid, self.submit_offset_ms(p)?)))
+          .collect()
+      }),
+      takes: can_see_all.then(|| {
+        self
+          .player_data
+          .iter()
+          .map(|(pid, p)| (*pid, p.takes.clone()))
+          .collect()
+      }),
+    }
+  }
+
+  pub fn transcript(
+    &self,
+    room_name: &str,
+    names: &HashMap<i64, super::room::RoomPlayer>,
+  ) -> String {
+    let name_of = |pid: i64| {
+      names
+        .get(&pid)
+        .map(|p| p.name.clone())
+        .unwrap_or_else(|| pid.to_string())
+    };
+
+    let mut out = format!("# Chain transcript: {}\n\n", room_name);
+    out += &format!("- Answer: {}\n", self.answer_text);
+    if !self.alt_answers.is_empty() {
+      out += &format!("- Also accepted: {}\n", self.alt_answers.join(", "));
+    }
+    let hint = self.hint_stages.text(&self.hint_text, true);
+    if !hint.is_empty() {
+      out += &format!("- Hint: {}\n", hint.replace('\n', " / "));
+    }
+    match &self.answer_regex {
+      Some(re) => out += &format!("- Matching: pattern `{}`\n", re.as_str()),
+      None => out += &format!("- Matching: {}\n", describe_strategy(self.match_strategy)),
+    }
+    out += "\n";
+
+    for pid in &self.players {
+      let Some(p) = self.player_data.get(pid) else {
+        continue;
+      };
+      out += &format!("## {}\n\n", name_of(*pid));
+      // Group consecutive takes by turn so a replay can follow the picking order
+      let mut turns: Vec<(usize, String)> = vec![];
+      for t in &p.takes {
+        let c = self.problem_text[t.index];
+        match turns.last_mut() {
+          Some((turn, chars)) if *turn == t.turn => chars.push(c),
+          _ => turns.push((t.turn, c.to_string())),
+        }
+      }
+      for (turn, chars) in turns {
+        out += &format!("- Turn {}: {}\n", turn, chars);
+      }
+      let answer = p.answer.as_deref().unwrap_or("");
+      let marks: String = self
+        .check_parts(answer)
+        .iter()
+        .map(|ok| if *ok { '✓' } else { '✗' })
+        .collect();
+      if self.answer_parts.len() > 1 {
+        out += &format!("- Answer: {} ({})", answer.replace('\n', " / "), marks);
+      } else {
+        out += &format!(
+          "- Answer: {} ({})",
+          p.answer.as_deref().unwrap_or("(none)"),
+          marks
+        );
+      }
+      if let Some(ms) = self.submit_offset_ms(p) {
+        out += &format!(" at {:+.1}s", ms as f64 / 1000.0);
+      }
+      out += "\n\n";
+    }
+    out
+  }
+
+  pub fn record(&self, names: &HashMap<i64, super::room::RoomPlayer>) -> GameRecordData {
+    GameRecordData::Chain {
+      problem: self.problem_text.iter().collect(),
+      answer: self.answer_text.clone(),
+      alt_answers: self.alt_answers.clone(),
+      hint: self.hint_stages.text(&self.hint_text, true),
+      answer_regex: self.answer_regex.as_ref().map(|re| re.as_str().to_string()),
+      players: self
+        .players
+        .iter()
+        .filter_map(|pid| {
+          let p = self.player_data.get(pid)?;
+          Some(ChainPlayerRecord {
+            id: *pid,
+            name: names.get(pid).map(|r| r.name.clone()).unwrap_or_default(),
+            answer: p.answer.clone(),
+            correct: self.check_parts(p.answer.as_deref().unwrap_or("")),
+            cells: p.obtained_indices.len(),
+            points: self.points(*pid),
+          })
+        })
+        .collect(),
+    }
   }
 
   pub fn get_player_state(
@@ -355,15 +990,21 @@ impl ChainGame {
     if let Some(p) = self.player_data.get(&pid) {
       let is_active =
         self.phase == GamePhase::Picking && self.players.get(self.current_turn_idx) == Some(&pid);
-      let score = format!("{}", p.obtained_indices.len());
+      // Cells taken while playing, points once it's over
+      let score = if self.phase == GamePhase::Settlement {
+        format!("{} pts", self.points(pid))
+      } else {
+        format!("{}", p.obtained_indices.len())
+      };
       let show_ans = show_all
+        || self.force_reveal
         || self.phase == GamePhase::Settlement
         || user_id == Some(pid)
-        || user_id.map_or(false, |u| {
+        || user_id.is_some_and(|u| {
           self
             .player_data
             .get(&u)
-            .map_or(false, |p| p.status == PlayerStatus::Submitted)
+            .is_some_and(|p| p.status == PlayerStatus::Submitted)
         });
       let ans = if show_ans { p.answer.clone() } else { None };
 
@@ -373,3 +1014,369 @@ impl ChainGame {
     }
   }
 }
+
+/// Case-insensitive form used to compare answers, trimmed and with each run of
+/// whitespace collapsed to one space, so words stay apart. NFKC folds
+/// full-width letters, digits, punctuation and spaces into their ASCII forms.
+pub fn normalize_answer(s: &str) -> String {
+  let folded: String = s.nfkc().flat_map(char::to_lowercase).collect();
+  folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Compiled pattern sizes are capped so a host can't make every check expensive
+const ANSWER_REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+/// Compile a host's answer pattern to match whole answers only. The pattern
+/// must be valid on its own, so stray parentheses can't close the anchoring
+/// group early and leave part of it unanchored.
+pub fn compile_answer_regex(pattern: &str) -> Result<Regex, String> {
+  let build = |p: &str| {
+    RegexBuilder::new(p)
+      .size_limit(ANSWER_REGEX_SIZE_LIMIT)
+      .build()
+      .map_err(|e| format!("Cannot start: invalid answer pattern ({})", e))
+  };
+  build(pattern)?;
+  build(&format!("^(?:{})$", pattern))
+}
+
+fn describe_strategy(s: MatchStrategy) -> String {
+  match s {
+    MatchStrategy::Exact => "exact".to_string(),
+    MatchStrategy::Normalized => "ignoring case, extra spaces and character width".to_string(),
+    MatchStrategy::Fuzzy { max_distance } => {
+      format!(
+        "within {} edit(s), ignoring case and extra spaces",
+        max_distance
+      )
+    }
+  }
+}
+
+/// Levenshtein distance in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b: Vec<char> = b.chars().collect();
+  let mut prev: Vec<usize> = (0..=b.len()).collect();
+  for (i, ca) in a.chars().enumerate() {
+    let mut cur = vec![i + 1; b.len() + 1];
+    for (j, cb) in b.iter().enumerate() {
+      let sub = prev[j] + usize::from(ca != *cb);
+      cur[j + 1] = sub.min(prev[j + 1] + 1).min(cur[j] + 1);
+    }
+    prev = cur;
+  }
+  prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::room::RoomPlayer;
+
+  fn channel() -> broadcast::Sender<InternalMsg> {
+    broadcast::channel(64).0
+  }
+
+  fn online(ids: &[i64]) -> HashMap<i64, RoomPlayer> {
+    ids
+      .iter()
+      .map(|id| {
+        (
+          *id,
+          RoomPlayer {
+            id: *id,
+            name: format!("p{}", id),
+            is_online: true,
+            is_spectator: false,
+            is_admin: false,
+            last_seen: Instant::now(),
+            ready: false,
+            resume_token: None,
+            resume_until: None,
+          },
+        )
+      })
+      .collect()
+  }
+
+  /// Seated in the given order, already picking.
+  fn started(problem: &str, answer: &str, players: &[i64]) -> ChainGame {
+    let mut game = ChainGame::new(problem.into(), answer.into(), String::new());
+    game.shuffle_order = false;
+    game.setup_players(players.to_vec());
+    game.start(&channel());
+    game
+  }
+
+  #[test]
+  fn timeout_take_clears_undo() {
+    let tx = channel();
+    let mut game = started("abcdef", "x", &[1, 2]);
+    game.handle_action(1, "take".into(), &tx);
+    assert!(game.can_undo(1));
+
+    game.turn_deadline = Some(Instant::now() - Duration::from_millis(10));
+    game.tick(&tx, &online(&[1, 2]), Duration::from_secs(30));
+    assert_eq!(game.player_data[&1].obtained_indices, vec![0, 1]);
+    assert!(!game.can_undo(1));
+
+    game.handle_action(1, "undo".into(), &tx);
+    assert_eq!(game.player_data[&1].obtained_indices, vec![0, 1]);
+    assert!(game.turn_deadline.is_some_and(|d| d > Instant::now()));
+  }
+
+  #[test]
+  fn rapid_takes_are_ignored() {
+    let tx = channel();
+    let mut game = started("abcdef", "x", &[1, 2]);
+    for _ in 0..5 {
+      game.handle_action(1, "take".into(), &tx);
+    }
+    assert_eq!(game.player_data[&1].obtained_indices, vec![0]);
+
+    // Once the interval has passed the next take goes through
+    let last = game.player_data[&1].last_take.unwrap();
+    game.player_data.get_mut(&1).unwrap().last_take = Some(last - game.min_take_interval);
+    game.handle_action(1, "take".into(), &tx);
+    assert_eq!(game.player_data[&1].obtained_indices, vec![0, 1]);
+  }
+
+  #[test]
+  fn timeout_takes_are_not_throttled() {
+    let tx = channel();
+    let mut game = started("abcdef", "x", &[1, 2]);
+    game.handle_action(1, "take".into(), &tx);
+    game.turn_deadline = Some(Instant::now() - Duration::from_millis(10));
+    game.tick(&tx, &online(&[1, 2]), Duration::from_secs(30));
+    assert_eq!(game.player_data[&1].obtained_indices, vec![0, 1]);
+  }
+
+  /// Take `n` cells for the current picker, clear of the take throttle.
+  fn take(game: &mut ChainGame, pid: i64, n: usize) {
+    for _ in 0..n {
+      game.player_data.get_mut(&pid).unwrap().last_take = None;
+      game.handle_action(pid, "take".into(), &channel());
+    }
+  }
+
+  #[test]
+  fn newlines_break_rows_and_are_never_taken() {
+    let tx = channel();
+    let mut game = started("ab\r\ncd", "x", &[1, 2, 3]);
+    assert_eq!(game.problem_text, vec!['a', 'b', '\n', 'c', 'd']);
+    take(&mut game, 1, 3);
+    assert_eq!(game.player_data[&1].obtained_indices, vec![0, 1, 3]);
+    game.handle_action(1, "stop".into(), &tx);
+
+    let view = game.get_view_data(Some(1), false, &HashMap::new());
+    let grid = view.grid.unwrap();
+    assert_eq!(grid.len(), 5);
+    assert!(grid[2].is_break);
+    assert_eq!(grid[2].char_content, None);
+    assert_eq!(grid.iter().filter(|c| c.is_break).count(), 1);
+    assert_eq!(grid[3].char_content, Some('c'));
+    assert_eq!(view.remaining_chars, Some(1));
+  }
+
+  #[test]
+  fn take_rest_and_hints_skip_newlines() {
+    let tx = channel();
+    let mut game = started("a\nb\nc", "x", &[1, 2]);
+    take(&mut game, 1, 1);
+    game.handle_action(1, "stop".into(), &tx);
+    // The last picker gets what's left, without the breaks
+    assert_eq!(game.player_data[&2].obtained_indices, vec![2, 4]);
+    assert_eq!(game.phase, GamePhase::Answering);
+
+    for _ in 0..5 {
+      game.reveal_hint(&tx);
+    }
+    assert_eq!(game.revealed_indices, HashSet::from([0, 2, 4]));
+  }
+
+  #[test]
+  fn stopped_players_are_skipped_and_may_answer_early() {
+    let tx = channel();
+    let mut game = started("abcdef", "x", &[1, 2, 3]);
+    take(&mut game, 1, 1);
+    game.handle_action(1, "stop".into(), &tx);
+    assert_eq!(game.player_data[&1].status, PlayerStatus::Stopped);
+    assert_eq!(game.players[game.current_turn_idx], 2);
+    assert!(game.can_answer(1));
+    assert!(!game.can_answer(3));
+
+    // Stopped players don't get another turn when it comes round again
+    take(&mut game, 2, 1);
+    game.handle_action(2, "stop".into(), &tx);
+    assert_eq!(game.player_data[&3].obtained_indices, vec![2, 3, 4, 5]);
+    assert_eq!(game.phase, GamePhase::Answering);
+    assert_eq!(game.player_data[&1].status, PlayerStatus::Answering);
+    assert_eq!(
+      serde_json::to_value(PlayerStatus::Stopped).unwrap(),
+      "stopped"
+    );
+  }
+
+  #[test]
+  fn early_answer_from_a_stopped_player_sticks() {
+    let tx = channel();
+    let mut game = started("abcdef", "x", &[1, 2, 3]);
+    game.handle_action(1, "stop".into(), &tx);
+    game.handle_answer(1, "x".into(), &tx);
+    assert_eq!(game.player_data[&1].status, PlayerStatus::Submitted);
+    game.handle_action(2, "stop".into(), &tx);
+    assert_eq!(game.phase, GamePhase::Answering);
+    assert_eq!(game.player_data[&1].status, PlayerStatus::Submitted);
+    assert_eq!(game.player_data[&2].status, PlayerStatus::Answering);
+  }
+
+  /// Answering, with the window opened a moment ago.
+  fn answering(players: &[i64]) -> ChainGame {
+    let mut game = started("abcdef", "answer", players);
+    game.phase = GamePhase::Answering;
+    game.answer_opened_at = Some(Instant::now());
+    game
+  }
+
+  /// Hand in `answer` for `pid`, `after` into the answer window.
+  fn submit(game: &mut ChainGame, pid: i64, answer: &str, after: Duration) {
+    let opened = game.answer_opened_at.unwrap();
+    game.handle_answer(pid, answer.into(), &channel());
+    game.player_data.get_mut(&pid).unwrap().submitted_at = Some(opened + after);
+  }
+
+  #[test]
+  fn points_reward_right_and_fast() {
+    let mut game = answering(&[1, 2, 3]);
+    submit(&mut game, 1, "answer", Duration::ZERO);
+    let half = game.answer_time / 2;
+    submit(&mut game, 2, "answer", half);
+    submit(&mut game, 3, "wrong", Duration::ZERO);
+    assert_eq!(game.points(1), BASE_POINTS + SPEED_BONUS);
+    assert_eq!(game.points(2), BASE_POINTS + SPEED_BONUS / 2);
+    assert_eq!(game.points(3), 0);
+  }
+
+  #[test]
+  fn hints_cost_the_speed_bonus() {
+    let mut game = answering(&[1, 2, 3]);
+    submit(&mut game, 1, "answer", Duration::ZERO);
+    game.revealed_indices.extend([0, 1]);
+    submit(&mut game, 2, "answer", Duration::ZERO);
+    game.revealed_indices.extend([2, 3, 4, 5]);
+    submit(&mut game, 3, "answer", Duration::ZERO);
+    // Reveals after an answer went in don't count against it
+    assert_eq!(game.points(1), BASE_POINTS + SPEED_BONUS);
+    assert_eq!(game.points(2), BASE_POINTS + SPEED_BONUS - 2 * HINT_PENALTY);
+    // Never below the base
+    assert_eq!(game.points(3), BASE_POINTS);
+  }
+
+  #[test]
+  fn leaderboard_shares_tied_ranks() {
+    let mut game = answering(&[1, 2, 3]);
+    submit(&mut game, 1, "answer", Duration::ZERO);
+    submit(&mut game, 2, "answer", Duration::ZERO);
+    let late = game.answer_time;
+    submit(&mut game, 3, "answer", late);
+    let board = game.leaderboard(&online(&[1, 2, 3]));
+    let ranks: Vec<(i64, usize)> = board.iter().map(|e| (e.id, e.rank)).collect();
+    assert_eq!(ranks, vec![(1, 1), (2, 1), (3, 3)]);
+  }
+
+  #[test]
+  fn leaderboard_keeps_unsubmitted_players_last() {
+    let mut game = answering(&[1, 2, 3]);
+    submit(&mut game, 2, "wrong", Duration::ZERO);
+    submit(&mut game, 3, "answer", Duration::ZERO);
+    let board = game.leaderboard(&online(&[1, 2, 3]));
+    assert_eq!(board[0].id, 3);
+    let unsubmitted = board.iter().find(|e| e.id == 1).unwrap();
+    assert!(!unsubmitted.submitted);
+    assert_eq!(unsubmitted.points, 0);
+    assert_eq!(unsubmitted.answer_ms, None);
+    // Nothing and a wrong answer tie at zero
+    assert_eq!(unsubmitted.rank, 2);
+  }
+
+  #[test]
+  fn edit_distance_counts_chars() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("abc", ""), 3);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    // One edit per character, not per byte
+    assert_eq!(edit_distance("北京大学", "北京大學"), 1);
+    assert_eq!(edit_distance("北京", ""), 2);
+    assert_eq!(edit_distance("中文", "文中"), 2);
+  }
+
+  #[test]
+  fn match_strategies() {
+    let mut game = started("abcdef", "Hello World", &[1]);
+    game.match_strategy = MatchStrategy::Exact;
+    assert!(game.check_parts("Hello World")[0]);
+    assert!(!game.check_parts("hello world")[0]);
+
+    game.match_strategy = MatchStrategy::Normalized;
+    assert!(game.check_parts(" hello   world ")[0]);
+    assert!(!game.check_parts("helloworld")[0]);
+    assert!(game.check_parts("ＨＥＬＬＯ　ｗｏｒｌｄ")[0]);
+    assert!(!game.check_parts("hello word")[0]);
+  }
+
+  #[test]
+  fn fuzzy_accepts_up_to_the_threshold() {
+    let mut game = started("abcdef", "北京大学", &[1]);
+    game.match_strategy = MatchStrategy::Fuzzy { max_distance: 1 };
+    assert!(game.check_parts("北京大学")[0]);
+    assert!(game.check_parts("北京大學")[0]);
+    assert!(game.check_parts("北京大")[0]);
+    assert!(!game.check_parts("北京")[0]);
+    assert!(!game.check_parts("")[0]);
+
+    game.match_strategy = MatchStrategy::Fuzzy { max_distance: 0 };
+    assert!(!game.check_parts("北京大學")[0]);
+  }
+
+  #[test]
+  fn answer_regex_matches_whole_answers() {
+    let re = compile_answer_regex(r"\d+(\.\d+)?\s*(kg)?").unwrap();
+    assert!(re.is_match("42"));
+    assert!(re.is_match("3.5 kg"));
+    assert!(!re.is_match("about 42"));
+    assert!(!re.is_match("42 kgs"));
+    // Alternatives are anchored as a group, not just the first and last
+    let re = compile_answer_regex("cat|dog").unwrap();
+    assert!(re.is_match("dog"));
+    assert!(!re.is_match("cats"));
+    assert!(!re.is_match("hotdog"));
+  }
+
+  #[test]
+  fn invalid_answer_regex_is_refused() {
+    assert!(compile_answer_regex("(unclosed").is_err());
+    assert!(compile_answer_regex("a{1000}{1000}").is_err());
+    // Balanced against the wrapping group, so this can't escape the anchors
+    assert!(compile_answer_regex(")|(.*").is_err());
+  }
+
+  #[test]
+  fn answer_regex_replaces_the_literal_answer() {
+    let mut game = started("abcdef", "42", &[1]);
+    assert!(game.check_parts("42")[0]);
+    assert!(!game.check_parts("42.0")[0]);
+    game.answer_regex = Some(compile_answer_regex(r"42(\.0+)?").unwrap());
+    assert!(game.check_parts("42.0")[0]);
+  }
+
+  #[test]
+  fn normalize_answer_folds_width_case_and_spaces() {
+    assert_eq!(normalize_answer("  Hello \t World \u{3000}"), "hello world");
+    assert_ne!(normalize_answer("ice cream"), normalize_answer("icecream"));
+    assert_eq!(normalize_answer("１２３４５"), "12345");
+    assert_eq!(normalize_answer("ＡＢＣ，！"), "abc,!");
+    // Characters without a narrow form are left alone
+    assert_eq!(normalize_answer("北京"), "北京");
+  }
+}
@@ -0,0 +1,84 @@
+//! Hints the host splits into stages that unlock while the game runs.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Stage `k` unlocks `k` intervals into the game, or as the `k`-th turn
+/// change happens when there is no interval. The plain hint is shown throughout.
+#[derive(Default, Serialize, Deserialize)]
+pub struct StagedHint {
+  pub stages: Vec<String>,
+  /// Stages unlocked so far
+  pub shown: usize,
+  /// Time between stages; `None` unlocks one per turn
+  pub interval: Option<Duration>,
+  #[serde(with = "super::snapshot::option_instant")]
+  pub next_at: Option<Instant>,
+}
+
+impl StagedHint {
+  pub fn new(stages: Vec<String>, interval: Option<Duration>) -> Self {
+    Self {
+      stages,
+      shown: 0,
+      interval,
+      next_at: None,
+    }
+  }
+
+  /// Start the clock for timed stages.
+  pub fn start(&mut self) {
+    self.schedule();
+  }
+
+  /// No more stages unlock, as at settlement.
+  pub fn stop(&mut self) {
+    self.next_at = None;
+  }
+
+  pub fn shift(&mut self, by: Duration) {
+    if let Some(t) = &mut self.next_at {
+      *t += by;
+    }
+  }
+
+  /// Unlock the next timed stage once it is due, returning it.
+  pub fn tick(&mut self, now: Instant) -> Option<String> {
+    if self.next_at.is_none_or(|t| now < t) {
+      return None;
+    }
+    self.unlock()
+  }
+
+  /// Unlock the next stage of a turn-paced hint, returning it.
+  pub fn next_turn(&mut self) -> Option<String> {
+    if self.interval.is_some() {
+      return None;
+    }
+    self.unlock()
+  }
+
+  fn unlock(&mut self) -> Option<String> {
+    let stage = self.stages.get(self.shown)?.clone();
+    self.shown += 1;
+    self.schedule();
+    Some(stage)
+  }
+
+  fn schedule(&mut self) {
+    self.next_at = self
+      .interval
+      .filter(|_| self.shown < self.stages.len())
+      .map(|d| Instant::now() + d);
+  }
+
+  /// `base`, then each stage a viewer may see on its own line.
+  pub fn text(&self, base: &str, full: bool) -> String {
+    let shown = if full { self.stages.len() } else { self.shown };
+    std::iter::once(base)
+      .filter(|b| !b.is_empty())
+      .chain(self.stages[..shown].iter().map(String::as_str))
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+}
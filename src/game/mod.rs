@@ -1,6 +1,10 @@
 pub mod chain;
+pub mod hint;
 pub mod pinyin;
 pub mod pinyin_utils;
 pub mod room;
+pub mod series;
+pub mod snapshot;
+pub mod stats;
 
 pub use crate::models::*;
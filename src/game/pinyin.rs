@@ -1,23 +1,61 @@
+use super::chain::normalize_answer;
+use super::hint::StagedHint;
 use super::pinyin_utils::{PinyinTable, get_text_components, validate_char};
 use crate::models::*;
 use chrono::Local;
 use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
 
+pub const DEFAULT_SKIP_LIMIT: usize = 1;
+pub const DEFAULT_TURN_TIME: Duration = Duration::from_secs(180);
+
+#[derive(Serialize, Deserialize)]
 pub struct PinyinGame {
   pub answer: String,
+  /// Other guesses that win; bans are only ever drawn from `answer`
+  pub alt_answers: Vec<String>,
   pub hint: String,
+  pub hint_stages: StagedHint,
+  /// Shared with the app; put back by `Room::restore`
+  #[serde(skip)]
   pub table: Arc<PinyinTable>,
+  /// Which of the two tables `table` is
+  pub tone_sensitive: bool,
   pub phase: GamePhase,
 
   pub players: Vec<i64>,
+  /// Shuffle the seating on start; off when the order was decided beforehand
+  pub shuffle_order: bool,
   pub player_data: HashMap<i64, PinyinPlayerState>,
+  /// Describers before the guesser; `None` seats every player
+  pub describer_count: Option<usize>,
+  pub sitting_out: Vec<i64>,
+  /// Skips allowed per game; the skip after the last one loses the game
+  pub skip_limit: usize,
+  pub skips_used: usize,
+  /// When false the guesser only gets the last description, not the history
+  pub guesser_sees_full_chain: bool,
+  pub turn_time: Duration,
+  /// Privately notify each player when their turn starts
+  pub turn_toast: bool,
+  /// Set by an admin at settlement to show the full chain to everyone
+  pub force_reveal: bool,
 
   pub current_idx: usize,
+  #[serde(with = "super::snapshot::option_instant")]
   pub turn_deadline: Option<Instant>,
+  /// Time left on the turn when its player dropped; the clock is stopped until they return
+  pub paused_remaining: Option<Duration>,
+  #[serde(with = "super::snapshot::option_instant")]
+  pub started_at: Option<Instant>,
+  /// Game clock reading, in ms, when the current turn began
+  pub turn_started_ms: Option<u64>,
+  /// How long the game ran, set at settlement
+  pub duration_ms: Option<u64>,
 
   pub history: Vec<PinyinHistoryItem>,
   pub banned_i: HashSet<String>,
@@ -32,10 +70,15 @@ pub struct PinyinGame {
   pub all_f: Vec<String>,
 
   pub winner: bool,
+  /// Why a lost game ended, when it wasn't simply a wrong guess or a timeout
+  pub end_reason: Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PinyinPlayerState {
   pub status: PlayerStatus,
+  /// Length of each turn this player has finished, in ms
+  pub turn_ms: Vec<u64>,
 }
 
 impl PinyinGame {
@@ -45,7 +88,7 @@ impl PinyinGame {
     // 预计算所有声韵母供前端显示
     let mut distinct_i = HashSet::new();
     let mut distinct_f = HashSet::new();
-    for (i, f) in table.values() {
+    for (i, f) in table.values().flatten() {
       distinct_i.insert(i.clone());
       distinct_f.insert(f.clone());
     }
@@ -56,13 +99,29 @@ impl PinyinGame {
 
     Self {
       answer: ans.clone(),
+      alt_answers: vec![],
       hint,
+      hint_stages: StagedHint::default(),
       table,
+      tone_sensitive: false,
       phase: GamePhase::Waiting,
       players: vec![],
+      shuffle_order: true,
       player_data: HashMap::new(),
+      describer_count: None,
+      sitting_out: vec![],
+      skip_limit: DEFAULT_SKIP_LIMIT,
+      skips_used: 0,
+      guesser_sees_full_chain: true,
+      turn_time: DEFAULT_TURN_TIME,
+      turn_toast: true,
+      force_reveal: false,
       current_idx: 0,
       turn_deadline: None,
+      paused_remaining: None,
+      started_at: None,
+      turn_started_ms: None,
+      duration_ms: None,
       history: vec![],
       banned_i: HashSet::new(),
       banned_f: HashSet::new(),
@@ -73,16 +132,18 @@ impl PinyinGame {
       all_i: v_i,
       all_f: v_f,
       winner: false,
+      end_reason: None,
     }
   }
 
   pub fn setup_players(&mut self, users: Vec<i64>) {
     for pid in users {
-      self.players.push(pid.clone());
+      self.players.push(pid);
       self.player_data.insert(
         pid,
         PinyinPlayerState {
           status: PlayerStatus::Waiting,
+          turn_ms: vec![],
         },
       );
     }
@@ -92,21 +153,37 @@ impl PinyinGame {
     if self.players.is_empty() {
       return;
     }
-    self.players.shuffle(&mut rand::thread_rng());
+    if self.shuffle_order {
+      self.players.shuffle(&mut rand::thread_rng());
+    }
+    // Only the first `describer_count` describers plus the guesser are seated
+    if let Some(n) = self.describer_count {
+      let seated = (n + 1).min(self.players.len());
+      self.sitting_out = self.players.split_off(seated);
+      for pid in &self.sitting_out {
+        if let Some(p) = self.player_data.get_mut(pid) {
+          p.status = PlayerStatus::SittingOut;
+        }
+      }
+    }
+    let _ = tx.send(InternalMsg::Phase {
+      from: self.phase.to_string(),
+      to: GamePhase::Gaming.to_string(),
+    });
     self.phase = GamePhase::Gaming;
+    self.started_at = Some(Instant::now());
+    self.turn_started_ms = Some(0);
+    self.hint_stages.start();
     self.current_idx = 0;
     self.current_prompt = self.answer.clone();
     self.is_first_describer = true;
+    self.skips_used = 0;
     self.banned_i.clear();
     self.banned_f.clear();
     self.history.clear();
 
-    if let Some(first) = self.players.first() {
-      if let Some(p) = self.player_data.get_mut(first) {
-        p.status = PlayerStatus::Picking; // Active
-      }
-    }
-    self.turn_deadline = Some(Instant::now() + Duration::from_secs(180));
+    self.activate_current(tx);
+    self.turn_deadline = Some(Instant::now() + self.turn_time);
     let _ = tx.send(InternalMsg::Log {
       who: "System".into(),
       text: "Pinyin game started".into(),
@@ -114,15 +191,111 @@ impl PinyinGame {
     });
   }
 
+  /// Seated players in turn order, followed by those sitting out.
+  pub fn seating_order(&self) -> Vec<i64> {
+    self
+      .players
+      .iter()
+      .chain(&self.sitting_out)
+      .cloned()
+      .collect()
+  }
+
   pub fn handle_join(&mut self, _: i64, _: &broadcast::Sender<InternalMsg>) {}
   pub fn handle_leave(&mut self, _: i64, _: &broadcast::Sender<InternalMsg>) {}
-  pub fn handle_action(&mut self, _: i64, _: String, _: &broadcast::Sender<InternalMsg>) {}
+  pub fn handle_action(&mut self, pid: i64, action: String, tx: &broadcast::Sender<InternalMsg>) {
+    if action == "skip" {
+      self.perform_skip(pid, tx);
+    }
+  }
 
-  pub fn handle_answer(&mut self, pid: i64, content: String, tx: &broadcast::Sender<InternalMsg>) {
-    if self.phase != GamePhase::Gaming {
+  /// Pass the turn on without describing; the prompt is handed over unchanged.
+  fn perform_skip(&mut self, pid: i64, tx: &broadcast::Sender<InternalMsg>) {
+    if self.phase != GamePhase::Gaming || self.players.get(self.current_idx) != Some(&pid) {
+      return;
+    }
+    // The guesser has nobody to pass to
+    if self.current_idx == self.players.len() - 1 {
+      return;
+    }
+
+    self.history.push(PinyinHistoryItem {
+      player: pid,
+      content: "(Skip)".into(),
+      is_guess: false,
+      elapsed_ms: self.elapsed_ms(),
+    });
+    if let Some(p) = self.player_data.get_mut(&pid) {
+      p.status = PlayerStatus::Described;
+    }
+
+    if self.skips_used >= self.skip_limit {
+      self.send_log(tx, format!("{} skipped with no skips left", pid));
+      self.finish(tx, false);
+      return;
+    }
+    self.skips_used += 1;
+    self.send_log(tx, format!("{} skipped", pid));
+    // `advance_turn` keeps the first-describer rule for whoever inherits the answer
+    self.advance_turn(tx);
+  }
+
+  /// Count the turn now ending towards its player's pace.
+  fn close_turn_clock(&mut self) {
+    let Some(from) = self.turn_started_ms.take() else {
       return;
+    };
+    let took = self.elapsed_ms().saturating_sub(from);
+    if let Some(pid) = self.players.get(self.current_idx)
+      && let Some(p) = self.player_data.get_mut(pid)
+    {
+      p.turn_ms.push(took);
     }
-    if self.players.get(self.current_idx) != Some(&pid) {
+  }
+
+  /// Seated players who took at least one turn, in seating order.
+  pub fn turn_times(&self, names: &HashMap<i64, super::room::RoomPlayer>) -> Vec<TurnTiming> {
+    self
+      .players
+      .iter()
+      .filter_map(|pid| {
+        let turns = &self.player_data.get(pid)?.turn_ms;
+        if turns.is_empty() {
+          return None;
+        }
+        Some(TurnTiming {
+          id: *pid,
+          name: names.get(pid).map(|r| r.name.clone()).unwrap_or_default(),
+          turns: turns.len(),
+          avg_ms: turns.iter().sum::<u64>() / turns.len() as u64,
+        })
+      })
+      .collect()
+  }
+
+  fn elapsed_ms(&self) -> u64 {
+    self
+      .started_at
+      .map_or(0, |t| t.elapsed().as_millis() as u64)
+  }
+
+  fn send_log(&self, tx: &broadcast::Sender<InternalMsg>, text: String) {
+    let _ = tx.send(InternalMsg::Log {
+      who: "System".into(),
+      text,
+      time: Local::now().format("%H:%M:%S").to_string(),
+    });
+  }
+
+  /// Only the player holding the turn may answer, and not while their turn is paused.
+  fn can_answer(&self, pid: i64) -> bool {
+    self.phase == GamePhase::Gaming
+      && self.players.get(self.current_idx) == Some(&pid)
+      && self.paused_remaining.is_none()
+  }
+
+  pub fn handle_answer(&mut self, pid: i64, content: String, tx: &broadcast::Sender<InternalMsg>) {
+    if !self.can_answer(pid) {
       return;
     }
     if content.trim().is_empty() {
@@ -132,35 +305,29 @@ impl PinyinGame {
     let is_guesser = self.current_idx == self.players.len() - 1;
 
     if is_guesser {
-      let win = content == self.answer;
+      let guess = normalize_answer(&content);
+      let win = std::iter::once(&self.answer)
+        .chain(&self.alt_answers)
+        .any(|a| normalize_answer(a) == guess);
       self.history.push(PinyinHistoryItem {
         player: pid,
         content: content.clone(),
         is_guess: true,
+        elapsed_ms: self.elapsed_ms(),
       });
       self.finish(tx, win);
     } else {
       // Validate Pinyin
-      for c in content.chars() {
-        if let Err(e) = validate_char(c, &self.table, &self.banned_i, &self.banned_f) {
-          let _ = tx.send(InternalMsg::Toast {
-            to_user: pid,
-            msg: e,
-            kind: "error".into(),
-          });
-          return;
-        }
-        if self.is_first_describer {
-          let (i, f) = &self.table[&c];
-          if self.answer_i.contains(i) || self.answer_f.contains(f) {
-            let _ = tx.send(InternalMsg::Toast {
-              to_user: pid,
-              msg: format!("Char '{}' invalid (in answer)", c),
-              kind: "error".into(),
-            });
-            return;
-          }
-        }
+      if let Some(e) = content
+        .chars()
+        .find_map(|c| self.char_error(c, true, self.is_first_describer))
+      {
+        let _ = tx.send(InternalMsg::Toast {
+          to_user: pid,
+          msg: e,
+          kind: "error".into(),
+        });
+        return;
       }
 
       // Update State
@@ -172,26 +339,117 @@ impl PinyinGame {
         player: pid,
         content: content.clone(),
         is_guess: false,
+        elapsed_ms: self.elapsed_ms(),
       });
       self.current_prompt = content;
       self.is_first_describer = false;
 
       if let Some(p) = self.player_data.get_mut(&pid) {
-        p.status = PlayerStatus::Submitted;
+        p.status = PlayerStatus::Described;
       }
       self.advance_turn(tx);
     }
   }
 
+  /// Why `c` can't be used in a description, if it can't.
+  fn char_error(&self, c: char, with_bans: bool, answer_banned: bool) -> Option<String> {
+    let empty = HashSet::new();
+    let (bi, bf) = if with_bans {
+      (&self.banned_i, &self.banned_f)
+    } else {
+      (&empty, &empty)
+    };
+    if let Err(e) = validate_char(c, &self.table, bi, bf) {
+      return Some(e);
+    }
+    if answer_banned
+      && !self.table[&c]
+        .iter()
+        .any(|r| self.reading_allowed(r, bi, bf, true))
+    {
+      return Some(format!("Char '{}' invalid (in answer)", c));
+    }
+    None
+  }
+
+  fn reading_allowed(
+    &self,
+    (i, f): &(String, String),
+    banned_i: &HashSet<String>,
+    banned_f: &HashSet<String>,
+    answer_banned: bool,
+  ) -> bool {
+    !banned_i.contains(i)
+      && !banned_f.contains(f)
+      && !(answer_banned && (self.answer_i.contains(i) || self.answer_f.contains(f)))
+  }
+
+  /// Check a planned description without submitting it, against the same bans
+  /// a submission would face. Only the describer holding the turn may ask.
+  pub fn validate_describe(&self, pid: i64, content: &str) -> Result<DescribeCheck, String> {
+    if self.phase != GamePhase::Gaming {
+      return Err("Game is not in progress".to_string());
+    }
+    let Some(my_idx) = self.players.iter().position(|p| *p == pid) else {
+      return Err("You are not playing this game".to_string());
+    };
+    if my_idx + 1 == self.players.len() {
+      return Err("The guesser does not describe".to_string());
+    }
+    if my_idx != self.current_idx {
+      return Err("It is not your turn to describe".to_string());
+    }
+    let chars = content
+      .chars()
+      .map(|ch| CharCheck {
+        ch,
+        error: self.char_error(ch, true, self.is_first_describer),
+      })
+      .collect();
+    Ok(DescribeCheck { chars })
+  }
+
+  /// Mark whoever holds the current turn as describing, or guessing if last.
+  fn activate_current(&mut self, tx: &broadcast::Sender<InternalMsg>) {
+    let status = if self.current_idx + 1 == self.players.len() {
+      PlayerStatus::Guessing
+    } else {
+      PlayerStatus::Describing
+    };
+    if let Some(pid) = self.players.get(self.current_idx)
+      && let Some(p) = self.player_data.get_mut(pid)
+    {
+      p.status = status;
+      if self.turn_toast {
+        let _ = tx.send(InternalMsg::Toast {
+          to_user: *pid,
+          msg: if status == PlayerStatus::Guessing {
+            "Your turn to guess!".into()
+          } else {
+            "Your turn to describe!".into()
+          },
+          kind: "turn".into(),
+        });
+      }
+    }
+  }
+
   fn advance_turn(&mut self, tx: &broadcast::Sender<InternalMsg>) {
+    self.close_turn_clock();
     self.current_idx += 1;
     if self.current_idx >= self.players.len() {
       self.finish(tx, false);
       return;
     }
-    let next = &self.players[self.current_idx];
-    if let Some(p) = self.player_data.get_mut(next) {
-      p.status = PlayerStatus::Picking;
+    if let Some(stage) = self.hint_stages.next_turn() {
+      self.send_log(tx, format!("New hint: {}", stage));
+    }
+    self.activate_current(tx);
+    if self.current_idx == self.players.len() - 1 {
+      let _ = tx.send(InternalMsg::Phase {
+        from: GamePhase::Gaming.to_string(),
+        to: "guessing".into(),
+      });
     }
 
     // Check "Prompt loop" logic: if prompt == answer (reset), first describer logic applies?
@@ -200,88 +458,246 @@ impl PinyinGame {
       self.is_first_describer = true;
     }
 
-    self.turn_deadline = Some(Instant::now() + Duration::from_secs(180));
+    self.turn_deadline = Some(Instant::now() + self.turn_time);
+    self.turn_started_ms = Some(self.elapsed_ms());
+
+    if !self.is_guessing_turn() && !self.has_legal_move() {
+      if self.skips_used >= self.skip_limit {
+        self.send_log(tx, "No legal moves remain and no skips are left".into());
+        self.end_reason = Some("no legal moves remain".into());
+        self.finish(tx, false);
+        return;
+      }
+      self.send_log(
+        tx,
+        "No legal characters remain; the describer can only skip".into(),
+      );
+    }
     let _ = tx.send(InternalMsg::StateUpdated);
   }
 
+  fn is_guessing_turn(&self) -> bool {
+    !self.players.is_empty() && self.current_idx == self.players.len() - 1
+  }
+
+  /// Whether any character in the table is still allowed for the current describer.
+  fn has_legal_move(&self) -> bool {
+    self
+      .table
+      .values()
+      .flatten()
+      .any(|r| self.reading_allowed(r, &self.banned_i, &self.banned_f, self.is_first_describer))
+  }
+
   fn finish(&mut self, tx: &broadcast::Sender<InternalMsg>, win: bool) {
+    let from = if self.current_idx == self.players.len() - 1 {
+      "guessing".to_string()
+    } else {
+      self.phase.to_string()
+    };
+    let _ = tx.send(InternalMsg::Phase {
+      from,
+      to: GamePhase::Settlement.to_string(),
+    });
+    self.close_turn_clock();
+    self.duration_ms = Some(self.elapsed_ms());
+    self.hint_stages.stop();
     self.phase = GamePhase::Settlement;
     self.winner = win;
     self.turn_deadline = None;
     let _ = tx.send(InternalMsg::StateUpdated);
   }
 
+  /// Push the turn timer back by `by`, after a pause. The game clock skips the pause too.
+  pub fn shift_deadlines(&mut self, by: Duration) {
+    for t in [&mut self.turn_deadline, &mut self.started_at]
+      .into_iter()
+      .flatten()
+    {
+      *t += by;
+    }
+    self.hint_stages.shift(by);
+  }
+
+  /// End the game now as a loss.
+  pub fn force_finish(&mut self, tx: &broadcast::Sender<InternalMsg>) -> Result<(), String> {
+    if self.phase != GamePhase::Gaming {
+      return Err("Game is not in progress".to_string());
+    }
+    self.finish(tx, false);
+    Ok(())
+  }
+
   pub fn tick(
     &mut self,
     tx: &broadcast::Sender<InternalMsg>,
     room_players: &HashMap<i64, super::room::RoomPlayer>,
+    grace: Duration,
   ) {
     if self.phase == GamePhase::Gaming {
       if self.current_idx >= self.players.len() {
         return;
       }
-      let curr = &self.players[self.current_idx];
-      let is_online = room_players.get(curr).map(|p| p.is_online).unwrap_or(false);
+      if let Some(stage) = self.hint_stages.tick(Instant::now()) {
+        self.send_log(tx, format!("New hint: {}", stage));
+        let _ = tx.send(InternalMsg::StateUpdated);
+      }
+      let curr = self.players[self.current_idx];
+      let (is_online, offline_for) = room_players
+        .get(&curr)
+        .map(|rp| (rp.is_online, rp.last_seen.elapsed()))
+        .unwrap_or((false, Duration::MAX));
 
       let mut timeout = false;
       if !is_online {
-        timeout = true;
-      }
-      if let Some(d) = self.turn_deadline {
-        if Instant::now() > d {
+        if offline_for > grace {
           timeout = true;
+        } else if self.paused_remaining.is_none() {
+          // Hold the turn for them while they may still reconnect
+          let now = Instant::now();
+          self.paused_remaining = self.turn_deadline.map(|d| d.saturating_duration_since(now));
+          self.turn_deadline = None;
+          self.send_log(tx, format!("{} is AFK, waiting to reconnect", curr));
+          let _ = tx.send(InternalMsg::StateUpdated);
         }
+      } else if let Some(remaining) = self.paused_remaining.take() {
+        self.turn_deadline = Some(Instant::now() + remaining);
+        self.send_log(tx, format!("{} is back, turn resumed", curr));
+        let _ = tx.send(InternalMsg::StateUpdated);
+      } else if let Some(d) = self.turn_deadline
+        && Instant::now() > d
+      {
+        timeout = true;
       }
 
       if timeout {
+        self.paused_remaining = None;
         // Timeout logic
         let is_guesser = self.current_idx == self.players.len() - 1;
         self.history.push(PinyinHistoryItem {
-          player: curr.clone(),
+          player: curr,
           content: "(Timeout)".into(),
           is_guess: is_guesser,
+          elapsed_ms: self.elapsed_ms(),
         });
         if is_guesser {
           self.finish(tx, false);
         } else {
+          if let Some(p) = self.player_data.get_mut(&curr) {
+            p.status = PlayerStatus::Described;
+          }
           self.advance_turn(tx);
         }
       }
     }
   }
 
+  pub fn record(&self, names: &HashMap<i64, super::room::RoomPlayer>) -> GameRecordData {
+    GameRecordData::Pinyin {
+      answer: self.answer.clone(),
+      alt_answers: self.alt_answers.clone(),
+      hint: self.hint_stages.text(&self.hint, true),
+      players: self
+        .players
+        .iter()
+        .map(|pid| PlayerRecord {
+          id: *pid,
+          name: names.get(pid).map(|r| r.name.clone()).unwrap_or_default(),
+        })
+        .collect(),
+      winner: self.winner,
+      end_reason: self.end_reason.clone(),
+      history: self.history.clone(),
+    }
+  }
+
+  /// Render the finished game as a markdown transcript, one line per turn.
+  pub fn transcript(
+    &self,
+    room_name: &str,
+    names: &HashMap<i64, super::room::RoomPlayer>,
+  ) -> String {
+    let name_of = |pid: i64| {
+      names
+        .get(&pid)
+        .map(|p| p.name.clone())
+        .unwrap_or_else(|| pid.to_string())
+    };
+
+    let mut out = format!("# Pinyin transcript: {}\n\n", room_name);
+    out += &format!("- Answer: {}\n", self.answer);
+    if !self.alt_answers.is_empty() {
+      out += &format!("- Also accepted: {}\n", self.alt_answers.join(", "));
+    }
+    let hint = self.hint_stages.text(&self.hint, true);
+    if !hint.is_empty() {
+      out += &format!("- Hint: {}\n", hint.replace('\n', " / "));
+    }
+    out += "\n";
+
+    let mut seen_i = self.answer_i.clone();
+    let mut seen_f = self.answer_f.clone();
+    for (round, item) in self.history.iter().enumerate() {
+      let role = if item.is_guess { "guess" } else { "describe" };
+      out += &format!(
+        "{}. [{}] **{}** ({}): {}",
+        round + 1,
+        format_elapsed(item.elapsed_ms),
+        name_of(item.player),
+        role,
+        item.content
+      );
+      if !item.is_guess {
+        let (ci, cf) = get_text_components(&item.content, &self.table);
+        let mut new_i: Vec<_> = ci.difference(&seen_i).cloned().collect();
+        let mut new_f: Vec<_> = cf.difference(&seen_f).cloned().collect();
+        new_i.sort();
+        new_f.sort();
+        if !new_i.is_empty() || !new_f.is_empty() {
+          out += &format!(
+            " (new initials: {}; new finals: {})",
+            new_i.join(" "),
+            new_f.join(" ")
+          );
+        }
+        seen_i.extend(ci);
+        seen_f.extend(cf);
+      }
+      out += "\n";
+    }
+
+    out += &format!(
+      "\nResult: {}\n",
+      if self.winner { "Success" } else { "Failed" }
+    );
+    out
+  }
+
   pub fn get_view_data(
     &self,
     user_id: Option<i64>,
     show_all: bool,
     _hue_map: &HashMap<i64, u16>,
-  ) -> (
-    GamePhase,
-    String,
-    Option<Instant>,
-    Option<Vec<GridCell>>,
-    Option<PinyinSpecificView>,
-    Option<bool>,
-    Option<String>,
-  ) {
+  ) -> GameViewData {
     let is_settled = self.phase == GamePhase::Settlement;
-    let can_see_all = show_all || is_settled;
+    let can_see_all = show_all || is_settled || self.force_reveal;
 
     // Visibility Logic for Bans:
     // 1. Super/Settled -> All
     // 2. Player: if my_idx <= current_idx (Past or Current) -> See Bans. Future -> Don't see.
     // 3. Spectator -> All
     let mut show_bans = can_see_all;
-    if !show_bans && user_id.is_some() {
-      if let Some(u) = user_id {
-        if let Some(my_idx) = self.players.iter().position(|p| *p == u) {
-          if my_idx <= self.current_idx {
-            show_bans = true;
-          }
-        } else {
-          // Authenticated user but not playing (spectator)
+    if !show_bans
+      && user_id.is_some()
+      && let Some(u) = user_id
+    {
+      if let Some(my_idx) = self.players.iter().position(|p| *p == u) {
+        if my_idx <= self.current_idx {
           show_bans = true;
         }
+      } else {
+        // Authenticated user but not playing (spectator)
+        show_bans = true;
       }
     }
 
@@ -297,23 +713,28 @@ impl PinyinGame {
     };
 
     // First describer strict logic: if I am the first describer, show answer bans in the ban list
-    if self.phase == GamePhase::Gaming && self.is_first_describer && user_id.is_some() {
-      if self.players.get(self.current_idx) == user_id.as_ref() {
-        b_i.extend(self.answer_i.clone());
-        b_f.extend(self.answer_f.clone());
-      }
+    if self.phase == GamePhase::Gaming
+      && self.is_first_describer
+      && user_id.is_some()
+      && self.players.get(self.current_idx) == user_id.as_ref()
+    {
+      b_i.extend(self.answer_i.clone());
+      b_f.extend(self.answer_f.clone());
     }
     b_i.sort();
     b_f.sort();
 
     // History Visibility:
     // Same logic: Past/Current players see history. Future don't.
+    // The guesser may be limited to the last description only.
+    let guesser = self.players.last();
+    let is_guesser = user_id.is_some() && guesser == user_id.as_ref();
     let mut visible_history = vec![];
     if can_see_all {
       visible_history = self.history.clone();
     } else if let Some(u) = user_id {
       if let Some(my_idx) = self.players.iter().position(|p| *p == u) {
-        if my_idx <= self.current_idx {
+        if my_idx <= self.current_idx && (!is_guesser || self.guesser_sees_full_chain) {
           visible_history = self.history.clone();
         }
       } else {
@@ -321,9 +742,11 @@ impl PinyinGame {
       }
     }
 
+    // If every describer skipped, the prompt is still the answer; never hand it to the guesser
     let my_prompt = if user_id.is_some()
       && self.phase == GamePhase::Gaming
       && self.players.get(self.current_idx) == user_id.as_ref()
+      && !(is_guesser && self.current_prompt == self.answer)
     {
       Some(self.current_prompt.clone())
     } else {
@@ -338,31 +761,52 @@ impl PinyinGame {
       history: visible_history,
       my_prompt,
       is_first_turn: self.is_first_describer,
-      is_guessing_turn: self.players.len() > 0 && self.current_idx == self.players.len() - 1,
+      tone_sensitive: self.tone_sensitive,
+      is_guessing_turn: self.is_guessing_turn(),
+      no_legal_moves: self.phase == GamePhase::Gaming
+        && !self.is_guessing_turn()
+        && user_id.is_some()
+        && self.players.get(self.current_idx) == user_id.as_ref()
+        && !self.has_legal_move(),
+      skips_remaining: self.skip_limit.saturating_sub(self.skips_used),
       end_message: if is_settled {
-        Some(if self.winner {
-          "Success".into()
-        } else {
-          "Failed".into()
+        Some(match (&self.end_reason, self.winner) {
+          (_, true) => "Success".into(),
+          (Some(reason), false) => format!("Failed: {}", reason),
+          (None, false) => "Failed".into(),
         })
       } else {
         None
       },
     };
 
-    (
-      self.phase,
-      self.hint.clone(),
-      self.turn_deadline,
-      None,
-      Some(pinyin_state),
-      Some(self.winner),
-      if can_see_all {
+    GameViewData {
+      phase: self.phase,
+      hint: self.hint_stages.text(&self.hint, can_see_all),
+      deadline: self.turn_deadline,
+      grid: None,
+      pinyin_state: Some(pinyin_state),
+      winner: Some(self.winner),
+      correct_answer: if can_see_all {
         Some(self.answer.clone())
       } else {
         None
       },
-    )
+      my_draft: None,
+      can_act: user_id.is_some_and(|u| self.can_answer(u) && !is_guesser),
+      can_answer: user_id.is_some_and(|u| self.can_answer(u)),
+      pending_submissions: None,
+      submit_ms: None,
+      takes: None,
+      answer_parts: None,
+      part_results: None,
+      match_strategy: None,
+      final_pick: false,
+      can_undo: false,
+      remaining_chars: None,
+      starting: false,
+      game_duration_ms: self.duration_ms,
+    }
   }
 
   pub fn get_player_state(
@@ -380,13 +824,332 @@ impl PinyinGame {
       self.phase == GamePhase::Gaming && self.players.get(self.current_idx) == Some(&pid);
 
     // In Pinyin, rounds are the score equivalent
-    let round_idx = self.players.iter().position(|p| *p == pid).unwrap_or(0);
-    let role = if round_idx == self.players.len().saturating_sub(1) {
-      "Guesser"
-    } else {
-      "Describer"
+    let role = match self.players.iter().position(|p| *p == pid) {
+      None if self.sitting_out.contains(&pid) => "Sitting Out",
+      Some(idx) if idx == self.players.len() - 1 => "Guesser",
+      _ => "Describer",
     };
 
     (p_status, Some(role.to_string()), is_active, None)
   }
 }
+
+/// `m:ss.s` for transcript timestamps.
+pub fn format_elapsed(ms: u64) -> String {
+  format!("{}:{:04.1}", ms / 60_000, (ms % 60_000) as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::room::RoomPlayer;
+
+  fn channel() -> broadcast::Sender<InternalMsg> {
+    broadcast::channel(64).0
+  }
+
+  /// 好 is the answer; the rest are there to describe it with.
+  fn table() -> Arc<PinyinTable> {
+    let reading = |i: &str, f: &str| vec![(i.to_string(), f.to_string())];
+    Arc::new(HashMap::from([
+      ('好', reading("h", "ao")),
+      ('大', reading("d", "a")),
+      ('天', reading("t", "ian")),
+      ('人', reading("r", "en")),
+    ]))
+  }
+
+  /// Seated in the given order, the last one guessing.
+  fn started(players: &[i64]) -> PinyinGame {
+    let mut game = PinyinGame::new("好".into(), String::new(), table());
+    game.shuffle_order = false;
+    game.setup_players(players.to_vec());
+    game.start(&channel());
+    game
+  }
+
+  /// What `pid` would be sent, as JSON.
+  fn view_json(game: &PinyinGame, pid: i64) -> String {
+    let view = game.get_view_data(Some(pid), false, &HashMap::new());
+    assert_eq!(view.correct_answer, None);
+    serde_json::to_string(&view.pinyin_state).unwrap()
+  }
+
+  #[test]
+  fn guesser_sees_the_chain_only_when_allowed() {
+    for full_chain in [false, true] {
+      let tx = channel();
+      let mut game = started(&[1, 2, 3]);
+      game.guesser_sees_full_chain = full_chain;
+      game.handle_answer(1, "大".into(), &tx);
+      game.handle_answer(2, "天".into(), &tx);
+
+      let view = game.get_view_data(Some(3), false, &HashMap::new());
+      let state = view.pinyin_state.unwrap();
+      assert_eq!(state.my_prompt.as_deref(), Some("天"));
+      assert_eq!(state.history.len(), if full_chain { 2 } else { 0 });
+      assert!(!view_json(&game, 3).contains('好'));
+    }
+  }
+
+  #[test]
+  fn guesser_never_gets_the_answer_as_a_prompt() {
+    let tx = channel();
+    let mut game = started(&[1, 2, 3]);
+    game.skip_limit = 2;
+    game.handle_action(1, "skip".into(), &tx);
+    game.handle_action(2, "skip".into(), &tx);
+    assert_eq!(game.players[game.current_idx], 3);
+
+    let state = game
+      .get_view_data(Some(3), false, &HashMap::new())
+      .pinyin_state
+      .unwrap();
+    assert_eq!(state.my_prompt, None);
+    assert!(!view_json(&game, 3).contains('好'));
+    // Describers are told the answer as their prompt, but only on their turn
+    assert!(!view_json(&game, 1).contains('好'));
+  }
+
+  fn statuses(game: &PinyinGame) -> Vec<PlayerStatus> {
+    game
+      .seating_order()
+      .iter()
+      .map(|pid| game.get_player_state(*pid, None, false).0)
+      .collect()
+  }
+
+  #[test]
+  fn statuses_follow_the_turn_through_a_game() {
+    use PlayerStatus::*;
+    let tx = channel();
+    let mut game = started(&[1, 2, 3]);
+    assert_eq!(statuses(&game), vec![Describing, Waiting, Waiting]);
+    game.handle_answer(1, "大".into(), &tx);
+    assert_eq!(statuses(&game), vec![Described, Describing, Waiting]);
+    game.handle_answer(2, "天".into(), &tx);
+    assert_eq!(statuses(&game), vec![Described, Described, Guessing]);
+
+    let roles: Vec<(bool, Option<String>)> = [1, 2, 3]
+      .iter()
+      .map(|pid| {
+        let (_, role, active, _) = game.get_player_state(*pid, None, false);
+        (active, role)
+      })
+      .collect();
+    assert_eq!(
+      roles,
+      vec![
+        (false, Some("Describer".into())),
+        (false, Some("Describer".into())),
+        (true, Some("Guesser".into())),
+      ]
+    );
+
+    game.handle_answer(3, "好".into(), &tx);
+    assert!(game.winner);
+    assert!(!game.get_player_state(3, None, false).2);
+  }
+
+  #[test]
+  fn players_past_the_describer_count_sit_out() {
+    use PlayerStatus::*;
+    let mut game = PinyinGame::new("好".into(), String::new(), table());
+    game.shuffle_order = false;
+    game.describer_count = Some(1);
+    game.setup_players(vec![1, 2, 3]);
+    game.start(&channel());
+    assert_eq!(statuses(&game), vec![Describing, Waiting, SittingOut]);
+    assert_eq!(
+      game.get_player_state(3, None, false).1.as_deref(),
+      Some("Sitting Out")
+    );
+  }
+
+  /// Everyone in `ids` as connected, except `away`, gone for `away_for`.
+  fn room_players(ids: &[i64], away: i64, away_for: Duration) -> HashMap<i64, RoomPlayer> {
+    ids
+      .iter()
+      .map(|id| {
+        let player = RoomPlayer {
+          id: *id,
+          name: format!("p{}", id),
+          is_online: *id != away,
+          is_spectator: false,
+          is_admin: false,
+          last_seen: Instant::now()
+            - if *id == away {
+              away_for
+            } else {
+              Duration::ZERO
+            },
+          ready: false,
+          resume_token: None,
+          resume_until: None,
+        };
+        (*id, player)
+      })
+      .collect()
+  }
+
+  const GRACE: Duration = Duration::from_secs(30);
+
+  /// Hand the turn to the guesser, 3.
+  fn guessing() -> PinyinGame {
+    let tx = channel();
+    let mut game = started(&[1, 2, 3]);
+    game.handle_answer(1, "大".into(), &tx);
+    game.handle_answer(2, "天".into(), &tx);
+    game
+  }
+
+  #[test]
+  fn guesser_reconnecting_within_grace_keeps_the_turn() {
+    let tx = channel();
+    let mut game = guessing();
+    let mut rx = tx.subscribe();
+    game.tick(
+      &tx,
+      &room_players(&[1, 2, 3], 3, Duration::from_secs(1)),
+      GRACE,
+    );
+    assert_eq!(game.phase, GamePhase::Gaming);
+    assert!(game.paused_remaining.is_some() && game.turn_deadline.is_none());
+    assert!(!game.can_answer(3));
+    let mut afk = false;
+    while let Ok(msg) = rx.try_recv() {
+      afk |= matches!(msg, InternalMsg::Log { text, .. } if text.contains("AFK"));
+    }
+    assert!(afk);
+
+    game.tick(&tx, &room_players(&[1, 2, 3], 0, Duration::ZERO), GRACE);
+    assert!(game.paused_remaining.is_none());
+    assert!(game.turn_deadline.is_some_and(|d| d > Instant::now()));
+    game.handle_answer(3, "好".into(), &tx);
+    assert!(game.winner);
+  }
+
+  #[test]
+  fn guesser_away_past_grace_loses() {
+    let tx = channel();
+    let mut game = guessing();
+    game.tick(
+      &tx,
+      &room_players(&[1, 2, 3], 3, Duration::from_secs(1)),
+      GRACE,
+    );
+    game.tick(&tx, &room_players(&[1, 2, 3], 3, GRACE * 2), GRACE);
+    assert_eq!(game.phase, GamePhase::Settlement);
+    assert!(!game.winner);
+    assert_eq!(game.history.last().unwrap().content, "(Timeout)");
+  }
+
+  /// Four seats where, once 1 describes with 天大, nothing is left for 2: the
+  /// answer's own initial was already banned.
+  fn exhausted(skip_limit: usize) -> PinyinGame {
+    let mut game = started(&[1, 2, 3, 4]);
+    game.skip_limit = skip_limit;
+    game.banned_i.insert("h".into());
+    game.banned_i.insert("r".into());
+    game.handle_answer(1, "天大".into(), &channel());
+    game
+  }
+
+  #[test]
+  fn no_legal_moves_leaves_only_a_skip() {
+    let tx = channel();
+    let mut game = exhausted(1);
+    assert_eq!(game.phase, GamePhase::Gaming);
+    assert!(!game.has_legal_move());
+    let state = |game: &PinyinGame, pid| {
+      game
+        .get_view_data(Some(pid), false, &HashMap::new())
+        .pinyin_state
+        .unwrap()
+    };
+    assert!(state(&game, 2).no_legal_moves);
+    // Only the describer holding the turn is told
+    assert!(!state(&game, 1).no_legal_moves);
+
+    // 3 is no better off, and that was the last skip
+    game.handle_action(2, "skip".into(), &tx);
+    assert_eq!(game.players[game.current_idx], 3);
+    assert_eq!(game.phase, GamePhase::Settlement);
+    assert_eq!(game.end_reason.as_deref(), Some("no legal moves remain"));
+  }
+
+  #[test]
+  fn no_legal_moves_and_no_skips_ends_the_game() {
+    let game = exhausted(0);
+    assert_eq!(game.phase, GamePhase::Settlement);
+    assert!(!game.winner);
+    assert_eq!(game.end_reason.as_deref(), Some("no legal moves remain"));
+    let state = game
+      .get_view_data(Some(2), false, &HashMap::new())
+      .pinyin_state
+      .unwrap();
+    assert_eq!(
+      state.end_message.as_deref(),
+      Some("Failed: no legal moves remain")
+    );
+  }
+
+  #[test]
+  fn only_the_current_describer_may_check() {
+    let tx = channel();
+    let mut game = started(&[1, 2, 3, 4]);
+    game.handle_answer(1, "大".into(), &tx);
+    assert_eq!(game.players[game.current_idx], 2);
+
+    assert!(game.validate_describe(1, "天").is_err());
+    assert!(game.validate_describe(3, "天").is_err());
+    assert!(game.validate_describe(4, "天").is_err());
+    let check = game.validate_describe(2, "天大").unwrap();
+    let errors: Vec<bool> = check.chars.iter().map(|c| c.error.is_some()).collect();
+    assert_eq!(errors, vec![false, true]);
+    // Nothing was submitted
+    assert_eq!(game.players[game.current_idx], 2);
+    assert!(!game.banned_i.contains("t"));
+  }
+
+  #[test]
+  fn first_describer_check_bans_the_answer() {
+    let game = started(&[1, 2]);
+    let check = game.validate_describe(1, "好人").unwrap();
+    let errors: Vec<bool> = check.chars.iter().map(|c| c.error.is_some()).collect();
+    assert_eq!(errors, vec![true, false]);
+  }
+
+  #[test]
+  fn describer_times_out_after_the_turn_time() {
+    let tx = channel();
+    let mut game = PinyinGame::new("好".into(), String::new(), table());
+    game.shuffle_order = false;
+    game.turn_time = Duration::from_secs(10);
+    game.setup_players(vec![1, 2, 3]);
+    game.start(&tx);
+    let deadline = game.turn_deadline.unwrap();
+    assert!(deadline <= Instant::now() + Duration::from_secs(10));
+    assert!(deadline > Instant::now() + Duration::from_secs(9));
+
+    let everyone = room_players(&[1, 2, 3], 0, Duration::ZERO);
+    game.tick(&tx, &everyone, GRACE);
+    assert_eq!(game.players[game.current_idx], 1);
+
+    game.turn_deadline = Some(Instant::now() - Duration::from_millis(10));
+    game.tick(&tx, &everyone, GRACE);
+    assert_eq!(game.players[game.current_idx], 2);
+    assert_eq!(game.history.last().unwrap().content, "(Timeout)");
+    assert_eq!(game.player_data[&1].status, PlayerStatus::Described);
+    // The next turn gets the same 10s
+    assert!(game.turn_deadline.unwrap() <= Instant::now() + Duration::from_secs(10));
+
+    // The guesser timing out ends the game
+    game.turn_deadline = Some(Instant::now() - Duration::from_millis(10));
+    game.tick(&tx, &everyone, GRACE);
+    game.turn_deadline = Some(Instant::now() - Duration::from_millis(10));
+    game.tick(&tx, &everyone, GRACE);
+    assert_eq!(game.phase, GamePhase::Settlement);
+    assert!(!game.winner);
+  }
+}
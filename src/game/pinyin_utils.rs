@@ -1,19 +1,90 @@
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::BufRead;
+use std::sync::Arc;
 
 pub type PinyinComponents = (String, String);
-pub type PinyinTable = HashMap<char, PinyinComponents>;
+/// Every reading of each character, most common first.
+pub type PinyinTable = HashMap<char, Vec<PinyinComponents>>;
 
-pub fn load_pinyin_table(path: &str) -> PinyinTable {
-  let file = match File::open(path) {
-    Ok(f) => f,
-    Err(_) => return HashMap::new(),
-  };
-  let reader = std::io::BufReader::new(file);
+/// The dictionary as loaded, where a tone digit (`hao3`) stays on the final,
+/// and the same with tones dropped for the default toneless rules.
+#[derive(Clone, Default)]
+pub struct PinyinTables {
+  pub toned: Arc<PinyinTable>,
+  pub toneless: Arc<PinyinTable>,
+}
+
+impl PinyinTables {
+  pub fn new(toned: PinyinTable) -> Self {
+    let toneless = strip_tones(&toned);
+    Self {
+      toned: Arc::new(toned),
+      toneless: Arc::new(toneless),
+    }
+  }
+
+  pub fn pick(&self, tone_sensitive: bool) -> Arc<PinyinTable> {
+    if tone_sensitive {
+      self.toned.clone()
+    } else {
+      self.toneless.clone()
+    }
+  }
+}
+
+/// Readings differing only in tone become one.
+fn strip_tones(table: &PinyinTable) -> PinyinTable {
+  table
+    .iter()
+    .map(|(c, readings)| {
+      let mut out: Vec<PinyinComponents> = vec![];
+      for (i, f) in readings {
+        let reading = (
+          i.clone(),
+          f.trim_end_matches(|d: char| d.is_ascii_digit()).to_string(),
+        );
+        if !out.contains(&reading) {
+          out.push(reading);
+        }
+      }
+      (*c, out)
+    })
+    .collect()
+}
+
+pub fn load_pinyin_table(path: &str) -> std::io::Result<PinyinTable> {
+  let (table, skipped) = parse_pinyin_table(std::io::BufReader::new(File::open(path)?))?;
+  if !skipped.is_empty() {
+    let lost_chars = skipped
+      .iter()
+      .map(|(c, _)| *c)
+      .filter(|c| !table.contains_key(c))
+      .collect::<HashSet<_>>()
+      .len();
+    let sample: Vec<String> = skipped
+      .iter()
+      .take(10)
+      .map(|(c, py)| format!("{},{}", c, py))
+      .collect();
+    tracing::warn!(
+      "{}: skipped {} reading(s) with no vowel, leaving out {} character(s) entirely; e.g. {}",
+      path,
+      skipped.len(),
+      lost_chars,
+      sample.join(" ")
+    );
+  }
+  Ok(table)
+}
+
+/// Read `char,pinyin,frequency` lines, returning the table and every reading
+/// that was left out because it has no vowel (hm, ng, ...) and can't be split,
+/// in char order.
+fn parse_pinyin_table(reader: impl BufRead) -> std::io::Result<(PinyinTable, Vec<(char, String)>)> {
   let mut raw_map: HashMap<char, Vec<(String, u64)>> = HashMap::new();
   for line in reader.lines() {
-    let line = line.expect("Read line");
+    let line = line?;
     let parts: Vec<&str> = line.split(',').collect();
     if parts.len() < 3 {
       continue;
@@ -26,15 +97,23 @@ pub fn load_pinyin_table(path: &str) -> PinyinTable {
     }
   }
   let mut table = HashMap::new();
+  let mut skipped: Vec<(char, String)> = vec![];
   for (c, mut list) in raw_map {
     list.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
-    if let Some((best_py, _)) = list.first() {
-      if let Some(comps) = split_pinyin(best_py) {
-        table.insert(c, comps);
+    let mut readings: Vec<PinyinComponents> = vec![];
+    for (py, _) in &list {
+      match split_pinyin(py) {
+        Some(comps) if !readings.contains(&comps) => readings.push(comps),
+        Some(_) => {}
+        None => skipped.push((c, py.clone())),
       }
     }
+    if !readings.is_empty() {
+      table.insert(c, readings);
+    }
   }
-  table
+  skipped.sort();
+  Ok((table, skipped))
 }
 
 fn split_pinyin(py: &str) -> Option<PinyinComponents> {
@@ -58,11 +137,9 @@ fn split_pinyin(py: &str) -> Option<PinyinComponents> {
 pub fn get_text_components(text: &str, table: &PinyinTable) -> (HashSet<String>, HashSet<String>) {
   let mut inits = HashSet::new();
   let mut finals = HashSet::new();
-  for c in text.chars() {
-    if let Some((i, f)) = table.get(&c) {
-      inits.insert(i.clone());
-      finals.insert(f.clone());
-    }
+  for (i, f) in text.chars().filter_map(|c| table.get(&c)).flatten() {
+    inits.insert(i.clone());
+    finals.insert(f.clone());
   }
   (inits, finals)
 }
@@ -73,15 +150,111 @@ pub fn validate_char(
   banned_inits: &HashSet<String>,
   banned_finals: &HashSet<String>,
 ) -> Result<(), String> {
-  if !table.contains_key(&c) {
+  let Some(readings) = table.get(&c) else {
     return Err(format!("Char '{}' invalid (not in table).", c));
+  };
+  // A polyphone is fine as long as one of its readings is
+  if readings
+    .iter()
+    .any(|(i, f)| !banned_inits.contains(i) && !banned_finals.contains(f))
+  {
+    return Ok(());
   }
-  let (i, f) = &table[&c];
+  let (i, f) = &readings[0];
   if banned_inits.contains(i) {
     return Err(format!("Char '{}' uses banned initial '{}'.", c, i));
   }
-  if banned_finals.contains(f) {
-    return Err(format!("Char '{}' uses banned final '{}'.", c, f));
+  Err(format!("Char '{}' uses banned final '{}'.", c, f))
+}
+
+/// Per-character view of how the table reads `text`, for the decompose API.
+pub fn decompose(text: &str, table: &PinyinTable) -> Vec<crate::models::CharDecomposition> {
+  text
+    .chars()
+    .map(|ch| crate::models::CharDecomposition {
+      ch,
+      decomposition: table.get(&ch).map(|readings| {
+        readings
+          .iter()
+          .map(|(i, f)| crate::models::PinyinParts {
+            pinyin: format!("{}{}", i, f),
+            initial: i.clone(),
+            fin: f.clone(),
+          })
+          .collect()
+      }),
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn reading(i: &str, f: &str) -> PinyinComponents {
+    (i.to_string(), f.to_string())
+  }
+
+  fn set(items: &[&str]) -> HashSet<String> {
+    items.iter().map(|s| s.to_string()).collect()
+  }
+
+  /// 长 is read chang (long) or zhang (to grow).
+  fn table() -> PinyinTable {
+    HashMap::from([
+      ('长', vec![reading("ch", "ang2"), reading("zh", "ang3")]),
+      ('大', vec![reading("d", "a4")]),
+    ])
+  }
+
+  #[test]
+  fn polyphones_pass_if_any_reading_does() {
+    let table = table();
+    assert!(validate_char('长', &table, &set(&["ch"]), &set(&[])).is_ok());
+    assert!(validate_char('长', &table, &set(&["zh"]), &set(&[])).is_ok());
+    let err = validate_char('长', &table, &set(&["ch", "zh"]), &set(&[])).unwrap_err();
+    assert!(err.contains("banned initial 'ch'"));
+    let err = validate_char('长', &table, &set(&[]), &set(&["ang2", "ang3"])).unwrap_err();
+    assert!(err.contains("banned final 'ang2'"));
+  }
+
+  #[test]
+  fn polyphones_ban_every_reading() {
+    let (inits, finals) = get_text_components("长大", &table());
+    assert_eq!(inits, set(&["ch", "zh", "d"]));
+    assert_eq!(finals, set(&["ang2", "ang3", "a4"]));
+  }
+
+  #[test]
+  fn vowelless_readings_are_reported_as_skipped() {
+    let dict = "嗯,ng,100\n嗯,en4,50\n呣,m,10\n好,hao3,900\n";
+    let (table, skipped) = parse_pinyin_table(dict.as_bytes()).unwrap();
+    assert_eq!(
+      skipped,
+      vec![('呣', "m".to_string()), ('嗯', "ng".to_string())]
+    );
+    // 嗯 keeps the reading that could be split; 呣 had none
+    assert_eq!(table[&'嗯'], vec![reading("", "en4")]);
+    assert!(!table.contains_key(&'呣'));
+    assert_eq!(table[&'好'], vec![reading("h", "ao3")]);
+  }
+
+  #[test]
+  fn readings_load_most_common_first() {
+    let dict = "长,zhang3,300\n长,chang2,900\nbad line\n长,chang2,1\n";
+    let (table, skipped) = parse_pinyin_table(dict.as_bytes()).unwrap();
+    assert!(skipped.is_empty());
+    assert_eq!(
+      table[&'长'],
+      vec![reading("ch", "ang2"), reading("zh", "ang3")]
+    );
+  }
+
+  #[test]
+  fn toneless_table_merges_readings_that_differ_in_tone_only() {
+    let toned = HashMap::from([('好', vec![reading("h", "ao3"), reading("h", "ao4")])]);
+    let tables = PinyinTables::new(toned);
+    assert_eq!(tables.toneless[&'好'], vec![reading("h", "ao")]);
+    assert_eq!(tables.toned[&'好'].len(), 2);
   }
-  Ok(())
 }
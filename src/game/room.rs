@@ -1,78 +1,435 @@
-use super::{chain::ChainGame, pinyin::PinyinGame};
-use crate::game::pinyin_utils::PinyinTable;
+use super::{
+  chain::{ChainGame, compile_answer_regex},
+  hint::StagedHint,
+  pinyin::PinyinGame,
+  series::{ChainMatch, MAX_MATCH_ROUNDS},
+  snapshot,
+  stats::PlayerOutcome,
+};
+use crate::conf::Config;
+use crate::game::pinyin_utils::PinyinTables;
 use crate::models::*;
+use axum::body::Bytes;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
-use std::sync::Arc;
-use std::time::Instant;
+use std::ops::RangeInclusive;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
+use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
 
+/// How long a disconnected player keeps their place in a running game before forfeiting,
+/// under the default reservation policy.
+pub const DISCONNECT_GRACE: Duration = Duration::from_secs(30);
+/// Accepted reservation lengths for `DisconnectPolicy::Reserve`, in seconds
+pub const RESERVE_SECS: RangeInclusive<u64> = 1..=600;
+/// How long a dropped connection may be picked up again with its resume token
+/// before the player is treated as having left.
+const RESUME_WINDOW: Duration = Duration::from_secs(10);
+
+/// Extra broadcast slots reserved per player seat on top of the configured capacity.
+const CHANNEL_SLOTS_PER_PLAYER: usize = 16;
+/// Accepted range for a room's grid cell size override, in pixels
+pub const CELL_SIZE_PX: RangeInclusive<u16> = 20..=80;
+
+/// Longest chat message kept, in chars
+const CHAT_MAX_CHARS: usize = 200;
+/// At most `CHAT_BURST` chat messages per user within `CHAT_WINDOW`
+const CHAT_BURST: usize = 3;
+const CHAT_WINDOW: Duration = Duration::from_secs(1);
+
+/// A cached view is only handed out this long after it was rendered, as the
+/// timers in it count from that moment
+const VIEW_MAX_AGE: Duration = Duration::from_millis(500);
+
+/// Accepted timer ranges in seconds, for both room defaults and per-game overrides
+pub const PICK_SECS: RangeInclusive<u64> = 1..=30;
+pub const ANSWER_SECS: RangeInclusive<u64> = 10..=600;
+pub const DESCRIBER_SECS: RangeInclusive<u64> = 15..=900;
+/// Accepted countdown before a chain game's first turn, in seconds; 0 starts at once
+pub const START_COUNTDOWN_SECS: RangeInclusive<u64> = 0..=10;
+
+#[derive(Serialize, Deserialize)]
 pub enum GameSession {
   None,
-  Chain(ChainGame),
-  Pinyin(PinyinGame),
+  Chain(Box<ChainGame>),
+  Pinyin(Box<PinyinGame>),
 }
 
+/// Serialized by `snapshot`; skipped fields are rebuilt by `restore`.
+#[derive(Serialize, Deserialize)]
 pub struct Room {
   pub id: Uuid,
   pub name: String,
   pub room_type: RoomType,
   pub max_players: usize,
+  /// Online spectators allowed at once, room admins aside; `None` is unlimited
+  pub max_spectators: Option<usize>,
   pub admin_ids: HashSet<i64>,
+  #[serde(skip, default = "detached_channel")]
   pub tx: broadcast::Sender<InternalMsg>,
   pub players: HashMap<i64, RoomPlayer>,
   pub session: GameSession,
+  #[serde(skip)]
+  pub max_problem_len: usize,
+  #[serde(skip)]
+  pub max_answer_len: usize,
+  #[serde(skip)]
+  pub max_message_bytes: usize,
+  /// Finished games are saved here, if set
+  #[serde(skip)]
+  pub results_dir: Option<String>,
+  /// Grid cell size for clients; `None` keeps the stylesheet default
+  pub cell_size_px: Option<u16>,
+  /// Used for any timer the start request leaves out
+  pub default_timers: Timers,
+  /// Setup saved ahead of time; kept across starts and stops until replaced or cleared
+  pub pending_game: Option<GameSetup>,
+  pub disconnect_policy: DisconnectPolicy,
+  /// Send each player a private "your turn" toast
+  pub turn_toasts: bool,
+  /// Countdown before the first chain turn, in seconds
+  pub start_countdown_secs: u64,
+  pub spectator_vision: SpectatorVision,
+  /// Judge chain answers exactly unless a game picks its own matching
+  pub strict_match: bool,
+  /// Pinyin bans tell tones apart, so `ma1` and `ma3` have different finals
+  pub tone_sensitive: bool,
+  /// Refuse to start until every seated player is ready
+  pub require_ready: bool,
+  /// Best-of-N chain match running in this room, or just finished
+  #[serde(skip)]
+  pub chain_match: Option<ChainMatch>,
+  /// When an admin paused the running game
+  #[serde(with = "snapshot::option_instant")]
+  pub paused_at: Option<Instant>,
+  /// Tentative seating for the next game from a reshuffle; empty leaves it to the start
+  pub planned_order: Vec<i64>,
+  /// What the lobby was last told about this room
+  #[serde(skip)]
+  pub last_summary: Option<RoomSummary>,
+  /// Invite-only rooms; admins never need it
+  pub join_password: Option<JoinPassword>,
+  /// Let anyone spectate a password-protected room
+  pub open_spectating: bool,
+  /// Users who have given the password, for this room's lifetime
+  pub admitted: HashSet<i64>,
+  /// Users kept out of this room by its admins, with their names at the time
+  pub banned: HashMap<i64, String>,
+  /// Since when nobody, player or spectator, has been connected
+  #[serde(skip)]
+  pub empty_since: Option<Instant>,
+  /// Recent chat send times per user, for rate limiting
+  #[serde(skip)]
+  chat_times: HashMap<i64, Vec<Instant>>,
+  /// Update frames already rendered since the last `StateUpdated`
+  #[serde(skip, default = "ViewCache::detached")]
+  view_cache: Mutex<ViewCache>,
+}
+
+/// Stand-in until `restore` connects a loaded room
+fn detached_channel() -> broadcast::Sender<InternalMsg> {
+  broadcast::channel(1).0
+}
+
+/// Whose eyes a view is rendered for. Plain spectators all see the same thing.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ViewKey {
+  Viewer(i64, bool),
+  Spectator,
+}
+
+/// Listens on the room channel like a socket does, so it is cleared by the same
+/// `StateUpdated` that makes the sockets ask for a view.
+struct ViewCache {
+  rx: broadcast::Receiver<InternalMsg>,
+  /// Each frame with when it was rendered
+  frames: HashMap<ViewKey, (Instant, Bytes)>,
+}
+
+impl ViewCache {
+  fn new(tx: &broadcast::Sender<InternalMsg>) -> Mutex<Self> {
+    Mutex::new(Self {
+      rx: tx.subscribe(),
+      frames: HashMap::new(),
+    })
+  }
+
+  fn detached() -> Mutex<Self> {
+    Self::new(&detached_channel())
+  }
 }
 
-#[derive(Clone)]
+/// Salted SHA-256 of a room password; the plain text is never kept.
+#[derive(Serialize, Deserialize)]
+pub struct JoinPassword {
+  salt: [u8; 16],
+  hash: [u8; 32],
+}
+
+impl JoinPassword {
+  pub fn new(password: &str) -> Self {
+    let salt: [u8; 16] = rand::random();
+    Self {
+      hash: Self::digest(&salt, password),
+      salt,
+    }
+  }
+
+  pub fn matches(&self, password: &str) -> bool {
+    Self::digest(&self.salt, password) == self.hash
+  }
+
+  fn digest(salt: &[u8], password: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(password.as_bytes());
+    hasher.finalize().into()
+  }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RoomPlayer {
   pub id: i64,
   pub name: String,
   pub is_online: bool,
   pub is_spectator: bool,
   pub is_admin: bool,
+  #[serde(with = "snapshot::instant")]
   pub last_seen: Instant,
+  /// Cleared whenever a game starts
+  pub ready: bool,
+  /// Handed to the player's current connection; a newer connection replaces it
+  #[serde(skip)]
+  pub resume_token: Option<Uuid>,
+  /// Set once the connection drops, until which it may still be resumed
+  #[serde(skip)]
+  pub resume_until: Option<Instant>,
 }
 
 impl Room {
-  pub fn new(id: Uuid, name: String, rtype: RoomType, max_players: usize, creator_id: i64) -> Self {
-    let (tx, _) = broadcast::channel(100);
+  pub fn new(
+    id: Uuid,
+    name: String,
+    rtype: RoomType,
+    max_players: usize,
+    creator_id: i64,
+    default_timers: Timers,
+    config: &Config,
+  ) -> Self {
+    let tx = Self::channel(config, max_players);
     let mut admins = HashSet::new();
     admins.insert(creator_id);
+    let view_cache = ViewCache::new(&tx);
 
     Self {
       id,
       name,
       room_type: rtype,
       max_players,
+      max_spectators: None,
       admin_ids: admins,
       tx,
       players: HashMap::new(),
       session: GameSession::None,
+      max_problem_len: config.max_problem_len,
+      max_answer_len: config.max_answer_len,
+      max_message_bytes: config.max_message_bytes,
+      results_dir: config.results_dir.clone(),
+      cell_size_px: None,
+      default_timers: default_timers.or(config.default_timers),
+      pending_game: None,
+      disconnect_policy: DisconnectPolicy::default(),
+      turn_toasts: true,
+      start_countdown_secs: 3,
+      spectator_vision: SpectatorVision::default(),
+      strict_match: false,
+      tone_sensitive: false,
+      require_ready: false,
+      chain_match: None,
+      paused_at: None,
+      planned_order: vec![],
+      last_summary: None,
+      join_password: None,
+      open_spectating: false,
+      admitted: HashSet::new(),
+      banned: HashMap::new(),
+      empty_since: Some(Instant::now()),
+      chat_times: HashMap::new(),
+      view_cache,
+    }
+  }
+
+  fn channel(config: &Config, max_players: usize) -> broadcast::Sender<InternalMsg> {
+    // Every player (and the spectators they bring) can queue a few messages per tick
+    broadcast::channel(config.room_channel_capacity.max(1) + max_players * CHANNEL_SLOTS_PER_PLAYER)
+      .0
+  }
+
+  /// Fill in what a snapshot leaves out, after loading one from disk.
+  pub fn restore(&mut self, config: &Config, pinyin_tables: PinyinTables) {
+    self.tx = Self::channel(config, self.max_players);
+    self.view_cache = ViewCache::new(&self.tx);
+    self.max_problem_len = config.max_problem_len;
+    self.max_answer_len = config.max_answer_len;
+    self.max_message_bytes = config.max_message_bytes;
+    self.results_dir = config.results_dir.clone();
+    self.empty_since = Some(Instant::now());
+    let now = Instant::now();
+    for p in self.players.values_mut() {
+      p.is_online = false;
+      p.last_seen = now;
+      p.ready = false;
+    }
+    if let GameSession::Pinyin(g) = &mut self.session {
+      g.table = pinyin_tables.pick(g.tone_sensitive);
+    }
+  }
+
+  pub fn phase(&self) -> GamePhase {
+    match &self.session {
+      GameSession::None => GamePhase::Waiting,
+      GameSession::Chain(g) => g.phase,
+      GameSession::Pinyin(g) => g.phase,
+    }
+  }
+
+  pub fn is_game_in_progress(&self) -> bool {
+    !matches!(self.phase(), GamePhase::Waiting | GamePhase::Settlement)
+  }
+
+  /// Turn toasts on or off, including for the game already running.
+  pub fn set_turn_toasts(&mut self, enabled: bool) {
+    self.turn_toasts = enabled;
+    match &mut self.session {
+      GameSession::Chain(g) => g.turn_toast = enabled,
+      GameSession::Pinyin(g) => g.turn_toast = enabled,
+      GameSession::None => {}
+    }
+  }
+
+  /// Set a player's ready flag. Ignored for spectators and while a game is running.
+  pub fn set_ready(&mut self, user_id: i64, ready: bool) {
+    if self.is_game_in_progress() {
+      return;
+    }
+    if let Some(p) = self.players.get_mut(&user_id)
+      && !p.is_spectator
+      && p.ready != ready
+    {
+      p.ready = ready;
+      let _ = self.tx.send(InternalMsg::StateUpdated);
+    }
+  }
+
+  /// Change the seat cap, at any time. It must stay at least one and at least
+  /// the number of seated players, offline ones included, so no change ever
+  /// unseats anyone.
+  pub fn set_max_players(&mut self, max: usize) -> Result<(), String> {
+    self.check_max_players(max)?;
+    self.max_players = max;
+    Ok(())
+  }
+
+  /// Whether `set_max_players(max)` would go through, without changing anything.
+  pub fn check_max_players(&self, max: usize) -> Result<(), String> {
+    if max == 0 {
+      return Err("A room needs room for at least one player".to_string());
+    }
+    let seated = self.players.values().filter(|p| !p.is_spectator).count();
+    if max < seated {
+      return Err(format!(
+        "Cannot shrink below the {} players already seated",
+        seated
+      ));
+    }
+    Ok(())
+  }
+
+  /// Replace the room's default timers. They can only change between games.
+  pub fn set_default_timers(&mut self, timers: Timers) -> Result<(), String> {
+    self.check_default_timers(&timers)?;
+    self.default_timers = timers;
+    Ok(())
+  }
+
+  /// Whether `set_default_timers(timers)` would go through, without changing anything.
+  pub fn check_default_timers(&self, timers: &Timers) -> Result<(), String> {
+    if *timers == self.default_timers {
+      return Ok(());
+    }
+    validate_timers(timers)?;
+    if self.is_game_in_progress() {
+      return Err("Default timers can only be changed between games".to_string());
     }
+    Ok(())
   }
 
+  /// Seat or reseat a connection. Returns the room channel and a token the
+  /// connection can present on its next connect to pick up where it left off.
   pub fn join(
     &mut self,
     user_id: i64,
     username: String,
     is_spectator: bool,
     is_site_admin: bool,
-  ) -> Result<broadcast::Receiver<InternalMsg>, String> {
+    resume: Option<Uuid>,
+  ) -> Result<(broadcast::Receiver<InternalMsg>, Uuid), String> {
     let rx = self.tx.subscribe();
     let now = Instant::now();
+    let token = Uuid::from_u128(rand::random());
 
     // 计算该用户在房间内的有效管理员权限
     let is_room_admin = self.admin_ids.contains(&user_id) || is_site_admin;
+    if self.banned.contains_key(&user_id) && !is_site_admin {
+      return Err("You are banned from this room".to_string());
+    }
+
+    // Spectators may watch mid-game, only held back by the spectator cap below.
+    // A spectator switching to playing is held to the same rules as a new player.
+    let needs_seat = !is_spectator && self.players.get(&user_id).is_none_or(|p| p.is_spectator);
+    if needs_seat {
+      if self.is_game_in_progress() {
+        return Err("Game is in progress".to_string());
+      }
+      let current_count = self.players.iter().filter(|p| !p.1.is_spectator).count();
+      if current_count >= self.max_players {
+        return Err("Room is full".to_string());
+      }
+    }
+    // Spectators leave the room entirely on disconnect, so only online ones hold a slot
+    if is_spectator
+      && !is_room_admin
+      && let Some(cap) = self.max_spectators
+    {
+      let watching = self
+        .players
+        .values()
+        .filter(|p| p.is_spectator && p.is_online && p.id != user_id)
+        .count();
+      if watching >= cap {
+        return Err("Too many spectators, try again later".to_string());
+      }
+    }
 
     if let Some(p) = self.players.get_mut(&user_id) {
       // Reconnect
+      let resumed = resume.is_some()
+        && p.resume_token == resume
+        && p.is_spectator == is_spectator
+        && p.resume_until.is_none_or(|t| now < t);
       p.is_online = true;
       p.last_seen = now;
+      p.resume_token = Some(token);
+      p.resume_until = None;
       // Update spectator/admin status on rejoin
       p.is_spectator = is_spectator;
       p.is_admin = is_room_admin;
-      if !is_spectator {
+      p.ready &= !is_spectator;
+      if !is_spectator && !resumed {
         let _ = self.tx.send(InternalMsg::Log {
           who: "System".into(),
           text: format!("{} reconnected", username),
@@ -81,32 +438,18 @@ impl Room {
       }
     } else {
       // New Join
-      let game_in_progress = match &self.session {
-        GameSession::None => false,
-        GameSession::Chain(g) => g.phase != GamePhase::Settlement,
-        GameSession::Pinyin(g) => g.phase != GamePhase::Settlement,
-      };
-
-      if !is_spectator && game_in_progress {
-        return Err("Game is in progress".to_string());
-      }
-
-      let current_count = self.players.iter().filter(|p| !p.1.is_spectator).count();
-
-      // Check Capacity
-      if !is_spectator && current_count >= self.max_players {
-        return Err("Room is full".to_string());
-      }
-
       self.players.insert(
-        user_id.clone(),
+        user_id,
         RoomPlayer {
-          id: user_id.clone(),
+          id: user_id,
           name: username.clone(),
           is_online: true,
           is_spectator,
           is_admin: is_room_admin,
           last_seen: now,
+          ready: false,
+          resume_token: Some(token),
+          resume_until: None,
         },
       );
       if !is_spectator {
@@ -125,23 +468,67 @@ impl Room {
     }
 
     let _ = self.tx.send(InternalMsg::StateUpdated);
-    Ok(rx)
+    Ok((rx, token))
   }
 
-  pub fn leave(&mut self, user_id: i64) {
+  /// A connection closed. The player goes offline at once, but keeps their
+  /// place and isn't announced as gone for `RESUME_WINDOW` in case they come
+  /// straight back, as on a page refresh. A close from a connection that has
+  /// since been replaced is ignored.
+  pub fn leave(&mut self, user_id: i64, token: Uuid) {
+    if let Some(p) = self.players.get_mut(&user_id)
+      && p.resume_token == Some(token)
+    {
+      let now = Instant::now();
+      p.is_online = false;
+      p.last_seen = now;
+      p.resume_until = Some(now + RESUME_WINDOW);
+      let _ = self.tx.send(InternalMsg::StateUpdated);
+    }
+  }
+
+  /// Let go of players whose dropped connection wasn't resumed in time.
+  fn expire_resumes(&mut self) {
+    let now = Instant::now();
+    let expired: Vec<i64> = self
+      .players
+      .values()
+      .filter(|p| p.resume_until.is_some_and(|t| now >= t))
+      .map(|p| p.id)
+      .collect();
+    for pid in expired {
+      self.drop_player(pid);
+    }
+  }
+
+  fn drop_player(&mut self, user_id: i64) {
     let is_waiting = matches!(self.session, GameSession::None);
+    let free_seat =
+      self.disconnect_policy == DisconnectPolicy::FreeImmediately && self.is_game_in_progress();
 
     if is_waiting {
       // 如果还在等待阶段，直接移除玩家，避免幽灵
       let _ = self.players.remove(&user_id);
     } else {
-      let is_spectator = self.players.get(&user_id).map_or(false, |p| p.is_spectator);
+      let is_spectator = self.players.get(&user_id).is_some_and(|p| p.is_spectator);
       if is_spectator {
         let _ = self.players.remove(&user_id);
+      } else if free_seat {
+        // 不保留座位：直接移除，游戏在下面的 tick 中跳过或判负
+        if let Some(p) = self.players.remove(&user_id) {
+          let _ = self.tx.send(InternalMsg::Log {
+            who: "System".into(),
+            text: format!("{} left room, seat freed", &p.name),
+            time: chrono::Local::now().format("%H:%M:%S").to_string(),
+          });
+        }
       } else if let Some(p) = self.players.get_mut(&user_id) {
         // 游戏进行中，标记为离线
-        p.is_online = false;
-        p.last_seen = Instant::now();
+        // A lapsed resume has been offline since `leave`, so keep that time
+        if p.resume_until.take().is_none() {
+          p.is_online = false;
+          p.last_seen = Instant::now();
+        }
         let _ = self.tx.send(InternalMsg::Log {
           who: "System".into(),
           text: format!("{} left room", &p.name),
@@ -155,12 +542,44 @@ impl Room {
       GameSession::Pinyin(g) => g.handle_leave(user_id, &self.tx),
       _ => {}
     }
+    if free_seat {
+      // Advance past the departed player now rather than on the next tick
+      self.tick_session();
+    }
     let _ = self.tx.send(InternalMsg::StateUpdated);
   }
 
+  /// Make `new_owner` a room admin, optionally removing `from` at the same time.
+  /// The new owner must be in the room, so the room never ends up without an admin.
+  pub fn transfer(&mut self, from: i64, new_owner: i64, step_down: bool) -> Result<(), String> {
+    if new_owner == from {
+      return Err("You can't transfer the room to yourself".to_string());
+    }
+    let Some(target) = self.players.get_mut(&new_owner) else {
+      return Err("The new owner must be in the room".to_string());
+    };
+    target.is_admin = true;
+    let name = target.name.clone();
+    self.admin_ids.insert(new_owner);
+    if step_down {
+      self.admin_ids.remove(&from);
+      // A site admin's flag comes back on their next join
+      if let Some(p) = self.players.get_mut(&from) {
+        p.is_admin = false;
+      }
+    }
+    let _ = self.tx.send(InternalMsg::Log {
+      who: "System".into(),
+      text: format!("{} is now a room admin", name),
+      time: chrono::Local::now().format("%H:%M:%S").to_string(),
+    });
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+    Ok(())
+  }
+
   pub fn kick(&mut self, user_id: i64) {
     // 1. 先执行离开逻辑，更新游戏内状态（如跳过回合）
-    self.leave(user_id);
+    self.drop_player(user_id);
 
     // 2. 从房间玩家列表中彻底移除 (防止 leave 逻辑仅仅标记为离线)
     self.players.remove(&user_id);
@@ -171,12 +590,51 @@ impl Room {
     let _ = self.tx.send(InternalMsg::StateUpdated);
   }
 
+  /// Kick someone and keep them from coming back. Room admins have to be
+  /// removed as admins first.
+  pub fn ban(&mut self, by: i64, user_id: i64, name: String) -> Result<(), String> {
+    if user_id == by {
+      return Err("You can't ban yourself".to_string());
+    }
+    if self.admin_ids.contains(&user_id) {
+      return Err("Room admins can't be banned".to_string());
+    }
+    if self.banned.insert(user_id, name.clone()).is_some() {
+      return Err(format!("{} is already banned", name));
+    }
+    self.admitted.remove(&user_id);
+    if self.players.contains_key(&user_id) {
+      self.kick(user_id);
+    }
+    let _ = self.tx.send(InternalMsg::Log {
+      who: "System".into(),
+      text: format!("{} was banned from the room", name),
+      time: chrono::Local::now().format("%H:%M:%S").to_string(),
+    });
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+    Ok(())
+  }
+
+  pub fn unban(&mut self, user_id: i64) -> Result<(), String> {
+    let Some(name) = self.banned.remove(&user_id) else {
+      return Err("That user isn't banned".to_string());
+    };
+    let _ = self.tx.send(InternalMsg::Log {
+      who: "System".into(),
+      text: format!("{} was unbanned", name),
+      time: chrono::Local::now().format("%H:%M:%S").to_string(),
+    });
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+    Ok(())
+  }
+
   /// Clean up players who are marked as offline
   fn kick_offline_players(&mut self) {
     let offline_ids: Vec<i64> = self
       .players
       .iter()
-      .filter(|(_, p)| !p.is_online)
+      // Whoever may still resume is only let go once the window lapses
+      .filter(|(_, p)| !p.is_online && p.resume_until.is_none())
       .map(|(k, _)| *k)
       .collect();
 
@@ -188,11 +646,14 @@ impl Room {
   }
 
   pub fn handle_action(&mut self, user_id: i64, action: String) {
+    if self.paused_at.is_some() {
+      return;
+    }
     // Spectators cannot act
-    if let Some(p) = self.players.get(&user_id) {
-      if p.is_spectator {
-        return;
-      }
+    if let Some(p) = self.players.get(&user_id)
+      && p.is_spectator
+    {
+      return;
     }
     match &mut self.session {
       GameSession::Chain(g) => g.handle_action(user_id, action, &self.tx),
@@ -202,11 +663,18 @@ impl Room {
   }
 
   pub fn handle_answer(&mut self, user_id: i64, content: String) {
-    if let Some(p) = self.players.get(&user_id) {
-      if p.is_spectator {
-        return;
-      }
+    if self.paused_at.is_some() {
+      return;
+    }
+    if let Some(p) = self.players.get(&user_id)
+      && p.is_spectator
+    {
+      return;
+    }
+    if self.refuse_oversized(user_id, &content, "Answer") {
+      return;
     }
+    let content = self.clean_answer(&content);
     match &mut self.session {
       GameSession::Chain(g) => g.handle_answer(user_id, content, &self.tx),
       GameSession::Pinyin(g) => g.handle_answer(user_id, content, &self.tx),
@@ -214,17 +682,257 @@ impl Room {
     }
   }
 
-  pub fn tick(&mut self, _global_tx: &broadcast::Sender<InternalMsg>) {
+  /// Tell the sender off and report true if `content` is over the size limit.
+  fn refuse_oversized(&self, user_id: i64, content: &str, what: &str) -> bool {
+    if content.len() <= self.max_message_bytes {
+      return false;
+    }
+    let _ = self.tx.send(InternalMsg::Toast {
+      to_user: user_id,
+      msg: format!(
+        "{} is too long (over {} bytes)",
+        what, self.max_message_bytes
+      ),
+      kind: "error".into(),
+    });
+    true
+  }
+
+  /// Relay a chat message. While a game runs, spectators only talk among themselves.
+  pub fn handle_chat(&mut self, user_id: i64, text: String) {
+    if self.refuse_oversized(user_id, &text, "Message") {
+      return;
+    }
+    let Some(p) = self.players.get(&user_id) else {
+      return;
+    };
+    let text: String = text
+      .chars()
+      .filter(|c| !c.is_control())
+      .take(CHAT_MAX_CHARS)
+      .collect();
+    let text = text.trim();
+    if text.is_empty() {
+      return;
+    }
+
+    let now = Instant::now();
+    let recent = self.chat_times.entry(user_id).or_default();
+    recent.retain(|t| now.duration_since(*t) < CHAT_WINDOW);
+    if recent.len() >= CHAT_BURST {
+      let _ = self.tx.send(InternalMsg::Toast {
+        to_user: user_id,
+        msg: "You're sending messages too fast".into(),
+        kind: "warning".into(),
+      });
+      return;
+    }
+    recent.push(now);
+
+    let _ = self.tx.send(InternalMsg::Chat {
+      from: p.name.clone(),
+      text: text.to_string(),
+      time: chrono::Local::now().format("%H:%M:%S").to_string(),
+      spectators_only: p.is_spectator && self.is_game_in_progress(),
+    });
+  }
+
+  pub fn handle_draft(&mut self, user_id: i64, content: String) {
+    if let Some(p) = self.players.get(&user_id)
+      && p.is_spectator
+    {
+      return;
+    }
+    // Drafts arrive with every keystroke; no need to complain about each one
+    if content.len() > self.max_message_bytes {
+      return;
+    }
+    let content = self.clean_answer(&content);
+    if let GameSession::Chain(g) = &mut self.session {
+      g.handle_draft(user_id, content);
+    }
+  }
+
+  /// Check a draft description the way submitting it would, without submitting.
+  pub fn validate_describe(&self, user_id: i64, content: &str) -> Result<DescribeCheck, String> {
+    let GameSession::Pinyin(g) = &self.session else {
+      return Err("Not a pinyin game".to_string());
+    };
+    g.validate_describe(user_id, &self.clean_answer(content))
+  }
+
+  /// Multi-part chain answers keep one line per blank; everything else is one line.
+  fn clean_answer(&self, content: &str) -> String {
+    match &self.session {
+      GameSession::Chain(g) if g.answer_parts.len() > 1 => content
+        .split('\n')
+        .take(g.answer_parts.len())
+        .map(|part| sanitize_answer(part, self.max_answer_len))
+        .collect::<Vec<_>>()
+        .join("\n"),
+      _ => sanitize_answer(content, self.max_answer_len),
+    }
+  }
+
+  /// Advance timers. Returns how each seated player did when the game settles
+  /// on this tick, for the cross-game stats.
+  pub fn tick(&mut self, global_tx: &broadcast::Sender<InternalMsg>) -> Option<Vec<PlayerOutcome>> {
+    self.expire_resumes();
+    self.tick_session();
+    self.tick_match();
+    if self.players.values().any(|p| p.is_online) {
+      self.empty_since = None;
+    } else {
+      self.empty_since.get_or_insert_with(Instant::now);
+    }
+    // New rooms, phase changes and joins all show up here within a tick
+    let summary = self.summary();
+    let mut outcomes = None;
+    if summary.phase == GamePhase::Settlement
+      && self
+        .last_summary
+        .as_ref()
+        .is_some_and(|s| s.phase != GamePhase::Settlement)
+    {
+      self.save_results();
+      outcomes = Some(self.outcomes());
+    }
+    if self.last_summary.as_ref() != Some(&summary) {
+      self.last_summary = Some(summary);
+      let _ = global_tx.send(InternalMsg::LobbyChanged);
+    }
+    outcomes
+  }
+
+  /// Seated players still here at the end. In chain the top scorers win and a
+  /// fully right answer is correct; in pinyin everyone shares the result and
+  /// the guesser gets the correct answer.
+  fn outcomes(&self) -> Vec<PlayerOutcome> {
+    let (seated, won, correct): (Vec<i64>, Vec<i64>, Vec<i64>) = match &self.session {
+      GameSession::Chain(g) => (
+        g.players.clone(),
+        g.leaderboard(&self.players)
+          .iter()
+          .filter(|e| e.rank == 1 && e.points > 0)
+          .map(|e| e.id)
+          .collect(),
+        g.winners(),
+      ),
+      GameSession::Pinyin(g) if g.winner => (
+        g.players.clone(),
+        g.players.clone(),
+        g.players.last().copied().into_iter().collect(),
+      ),
+      GameSession::Pinyin(g) => (g.players.clone(), vec![], vec![]),
+      GameSession::None => return vec![],
+    };
+    seated
+      .into_iter()
+      .filter_map(|pid| {
+        let rp = self.players.get(&pid)?;
+        (rp.is_online && !rp.is_spectator).then(|| PlayerOutcome {
+          id: pid,
+          name: rp.name.clone(),
+          won: won.contains(&pid),
+          correct: correct.contains(&pid),
+        })
+      })
+      .collect()
+  }
+
+  /// Write the settled game to the results directory in the background.
+  fn save_results(&self) {
+    let Some(dir) = self.results_dir.clone() else {
+      return;
+    };
+    let game = match &self.session {
+      GameSession::Chain(g) => g.record(&self.players),
+      GameSession::Pinyin(g) => g.record(&self.players),
+      GameSession::None => return,
+    };
+    let record = GameRecord {
+      room_id: self.id.to_string(),
+      room_name: self.name.clone(),
+      finished_at: chrono::Utc::now(),
+      game,
+    };
+    let json = match serde_json::to_string_pretty(&record) {
+      Ok(j) => j,
+      Err(e) => {
+        tracing::warn!("failed to serialize results for room {}: {}", self.id, e);
+        return;
+      }
+    };
+    let path = std::path::Path::new(&dir).join(format!(
+      "{}-{}.json",
+      record.finished_at.format("%Y%m%dT%H%M%SZ"),
+      self.id
+    ));
+    tokio::spawn(async move {
+      let written = match tokio::fs::create_dir_all(&dir).await {
+        Ok(()) => tokio::fs::write(&path, json).await,
+        Err(e) => Err(e),
+      };
+      if let Err(e) = written {
+        tracing::warn!("failed to write {}: {}", path.display(), e);
+      }
+    });
+  }
+
+  /// Empty for at least `idle` with no game running. Seats reserved for a
+  /// reconnect only exist mid-game, so those rooms are never idle.
+  pub fn is_idle(&self, idle: Duration) -> bool {
+    !self.is_game_in_progress() && self.empty_since.is_some_and(|t| t.elapsed() >= idle)
+  }
+
+  /// Whether the user may open this room without giving the password.
+  pub fn can_enter(&self, user_id: i64, is_site_admin: bool, spectate: bool) -> bool {
+    self.join_password.is_none()
+      || is_site_admin
+      || self.admin_ids.contains(&user_id)
+      || (spectate && self.open_spectating)
+      || self.admitted.contains(&user_id)
+  }
+
+  /// Check a password and remember the user if it's right.
+  pub fn admit(&mut self, user_id: i64, password: &str) -> bool {
+    let ok = self
+      .join_password
+      .as_ref()
+      .is_none_or(|p| p.matches(password));
+    if ok {
+      self.admitted.insert(user_id);
+    }
+    ok
+  }
+
+  pub fn summary(&self) -> RoomSummary {
+    RoomSummary {
+      id: self.id.to_string(),
+      name: self.name.clone(),
+      room_type: self.room_type,
+      phase: self.phase(),
+      player_count: self.players.values().filter(|p| !p.is_spectator).count(),
+      max_players: self.max_players,
+      locked: self.join_password.is_some(),
+    }
+  }
+
+  fn tick_session(&mut self) {
+    if self.paused_at.is_some() {
+      return;
+    }
+    let grace = self.disconnect_policy.grace();
     let mut should_clean = false;
     match &mut self.session {
       GameSession::Chain(g) => {
-        g.tick(&self.tx, &self.players);
+        g.tick(&self.tx, &self.players, grace);
         if g.phase == GamePhase::Settlement {
           should_clean = true;
         }
       }
       GameSession::Pinyin(g) => {
-        g.tick(&self.tx, &self.players);
+        g.tick(&self.tx, &self.players, grace);
         if g.phase == GamePhase::Settlement {
           should_clean = true;
         }
@@ -237,19 +945,269 @@ impl Room {
     }
   }
 
-  pub fn start_game(
+  /// Start a one-off game, ending any match in the room.
+  pub fn start_game(&mut self, setup: GameSetup, pinyin_tables: PinyinTables) {
+    self.chain_match = None;
+    self.begin_game(setup, pinyin_tables);
+  }
+
+  /// Play the problems in order as a best-of-N chain match.
+  pub fn start_match(
     &mut self,
-    problem: String,
-    answer: String,
-    hint: String,
-    pinyin_table: Arc<PinyinTable>,
-  ) {
-    // Filter active players (online AND not spectator)
-    let mut active_players = Vec::new();
-    for (pid, p) in &self.players {
-      if p.is_online && !p.is_spectator {
-        active_players.push(pid.clone());
+    problems: Vec<GameSetup>,
+    intermission: Duration,
+    pinyin_tables: PinyinTables,
+  ) -> Result<(), String> {
+    if self.room_type != RoomType::Chain {
+      return Err("Matches are only for chain rooms".to_string());
+    }
+    if self.is_game_in_progress() {
+      return Err("Game is in progress".to_string());
+    }
+    if problems.is_empty() || problems.len() > MAX_MATCH_ROUNDS {
+      return Err(format!(
+        "A match needs between 1 and {} problems",
+        MAX_MATCH_ROUNDS
+      ));
+    }
+    self.chain_match = Some(ChainMatch::new(problems, intermission, pinyin_tables));
+    self.next_round();
+    Ok(())
+  }
+
+  /// Launch the match's next problem, giving up on the match if it can't start.
+  fn next_round(&mut self) {
+    let Some(m) = &mut self.chain_match else {
+      return;
+    };
+    m.next_round_at = None;
+    let (Some(setup), table) = (m.upcoming.pop_front(), m.table.clone()) else {
+      return;
+    };
+    self.begin_game(setup, table);
+    let launched = self.is_game_in_progress();
+    match &mut self.chain_match {
+      Some(m) if launched => {
+        m.started += 1;
+        let text = format!("Round {} of {}", m.started, m.rounds);
+        self.log(text);
+      }
+      _ => {
+        self.chain_match = None;
+        self.log("Match abandoned: the next round could not start".into());
+      }
+    }
+  }
+
+  /// Score settled rounds and start the next one once the intermission is over.
+  fn tick_match(&mut self) {
+    let (Some(m), GameSession::Chain(g)) = (&mut self.chain_match, &self.session) else {
+      return;
+    };
+    if g.phase == GamePhase::Settlement && m.scored < m.started {
+      let winners = g.winners();
+      m.record_round(&winners);
+      self.announce_round(&winners);
+    } else if m.next_round_at.is_some_and(|t| Instant::now() >= t) {
+      self.next_round();
+    }
+  }
+
+  fn announce_round(&self, winners: &[i64]) {
+    let Some(m) = &self.chain_match else {
+      return;
+    };
+    let names = self.names_of(winners);
+    let round = format!(
+      "Round {}: {}",
+      m.scored,
+      if names.is_empty() {
+        "nobody got it".to_string()
+      } else {
+        format!("{} got it", names)
       }
+    );
+    let result = m.finished.then(|| {
+      let leaders = m.leaders();
+      match leaders.first() {
+        Some(first) => format!(
+          "Match over: {} with {} win(s)",
+          self.names_of(&leaders),
+          m.wins_of(*first)
+        ),
+        None => "Match over: nobody won a round".to_string(),
+      }
+    });
+    self.log(round);
+    if let Some(msg) = result {
+      self.log(msg.clone());
+      let _ = self.tx.send(InternalMsg::Toast {
+        to_user: 0,
+        msg,
+        kind: "success".into(),
+      });
+    }
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+  }
+
+  fn names_of(&self, ids: &[i64]) -> String {
+    ids
+      .iter()
+      .map(|id| {
+        self
+          .players
+          .get(id)
+          .map(|p| p.name.clone())
+          .unwrap_or_else(|| id.to_string())
+      })
+      .collect::<Vec<_>>()
+      .join(", ")
+  }
+
+  fn log(&self, text: String) {
+    let _ = self.tx.send(InternalMsg::Log {
+      who: "System".into(),
+      text,
+      time: chrono::Local::now().format("%H:%M:%S").to_string(),
+    });
+  }
+
+  fn begin_game(&mut self, setup: GameSetup, pinyin_tables: PinyinTables) {
+    let active_players = self.active_player_ids();
+    if self.planned_order.is_empty() {
+      self.launch_game(active_players, true, setup, pinyin_tables);
+      return;
+    }
+    let order = seat_in_order(&self.planned_order, active_players);
+    self.launch_game(order, false, setup, pinyin_tables);
+    // The plan is used up once a game actually starts with it
+    if self.is_game_in_progress() {
+      self.planned_order.clear();
+    }
+  }
+
+  /// Draw a fresh tentative seating for the next game and show it to everyone.
+  pub fn reshuffle(&mut self) -> Result<(), String> {
+    if self.is_game_in_progress() {
+      return Err("Game is in progress".to_string());
+    }
+    let mut order = self.active_player_ids();
+    order.shuffle(&mut rand::thread_rng());
+    self.planned_order = order;
+    let _ = self.tx.send(InternalMsg::Log {
+      who: "System".into(),
+      text: "Seating order reshuffled".into(),
+      time: chrono::Local::now().format("%H:%M:%S").to_string(),
+    });
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+    Ok(())
+  }
+
+  pub fn set_pending_game(&mut self, setup: Option<GameSetup>) {
+    self.pending_game = setup;
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+  }
+
+  /// Start a game from the prepared setup, which stays available for the next round.
+  pub fn start_prepared(&mut self, pinyin_tables: PinyinTables) -> Result<(), String> {
+    let Some(setup) = self.pending_game.clone() else {
+      return Err("No game has been prepared".to_string());
+    };
+    self.start_game(setup, pinyin_tables);
+    Ok(())
+  }
+
+  /// Start another game with the last game's roster, keeping its seating order.
+  /// Players who joined since are seated after the returning ones.
+  pub fn rematch(&mut self, setup: GameSetup, pinyin_tables: PinyinTables) -> Result<(), String> {
+    if self.is_game_in_progress() {
+      return Err("Game is in progress".to_string());
+    }
+    if setup.answer.is_empty() || (self.room_type == RoomType::Chain && setup.problem.is_empty()) {
+      return Err("A rematch needs a prepared problem".to_string());
+    }
+
+    let active_players = self.active_player_ids();
+    let previous = match &self.session {
+      GameSession::Chain(g) => g.players.clone(),
+      GameSession::Pinyin(g) => g.seating_order(),
+      GameSession::None => vec![],
+    };
+    // Without a previous order to keep, fall back to a fresh shuffle
+    let shuffle = !previous.iter().any(|pid| active_players.contains(pid));
+    let order = seat_in_order(&previous, active_players);
+    self.chain_match = None;
+
+    self.launch_game(order, shuffle, setup, pinyin_tables);
+    Ok(())
+  }
+
+  /// Players who can be seated: online and not spectating.
+  fn active_player_ids(&self) -> Vec<i64> {
+    self
+      .players
+      .iter()
+      .filter(|(_, p)| p.is_online && !p.is_spectator)
+      .map(|(pid, _)| *pid)
+      .collect()
+  }
+
+  fn launch_game(
+    &mut self,
+    active_players: Vec<i64>,
+    shuffle: bool,
+    setup: GameSetup,
+    pinyin_tables: PinyinTables,
+  ) {
+    let GameSetup {
+      problem,
+      answer,
+      answers,
+      hint,
+      options,
+    } = setup;
+
+    let hint_stages = StagedHint::new(
+      options.hint_stages.clone(),
+      options
+        .hint_stage_secs
+        .filter(|s| *s > 0)
+        .map(Duration::from_secs),
+    );
+    let problem_len = problem.graphemes(true).count();
+    let length_error = if self.room_type == RoomType::Chain && problem_len > self.max_problem_len {
+      Some(format!(
+        "Cannot start: problem is {} characters long (max {}).",
+        problem_len, self.max_problem_len
+      ))
+    } else if std::iter::once(&answer)
+      .chain(&answers)
+      .any(|a| a.chars().count() > self.max_answer_len)
+    {
+      Some(format!(
+        "Cannot start: answer is longer than {} characters.",
+        self.max_answer_len
+      ))
+    } else {
+      None
+    };
+    if let Some(msg) = length_error {
+      let _ = self.tx.send(InternalMsg::Toast {
+        to_user: 0,
+        msg,
+        kind: "error".into(),
+      });
+      return;
+    }
+
+    let timers = options.timers.or(self.default_timers);
+    if let Err(e) = validate_timers(&timers) {
+      let _ = self.tx.send(InternalMsg::Toast {
+        to_user: 0,
+        msg: format!("Cannot start: {}", e),
+        kind: "error".into(),
+      });
+      return;
     }
 
     if active_players.is_empty() {
@@ -261,53 +1219,291 @@ impl Room {
       return;
     }
 
+    // Later rounds of a match follow on without another ready check
+    if self.require_ready && self.chain_match.as_ref().is_none_or(|m| m.started == 0) {
+      let mut not_ready: Vec<&str> = active_players
+        .iter()
+        .filter_map(|pid| self.players.get(pid))
+        .filter(|p| !p.ready)
+        .map(|p| p.name.as_str())
+        .collect();
+      if !not_ready.is_empty() {
+        not_ready.sort();
+        let _ = self.tx.send(InternalMsg::Toast {
+          to_user: 0,
+          msg: format!("Cannot start: not ready yet: {}.", not_ready.join(", ")),
+          kind: "error".into(),
+        });
+        return;
+      }
+    }
+
     match self.room_type {
       RoomType::Chain => {
+        let parts: Vec<String> = options
+          .answer_parts
+          .iter()
+          .map(|p| p.trim().to_string())
+          .filter(|p| !p.is_empty())
+          .collect();
+        let pattern = options
+          .answer_regex
+          .as_deref()
+          .map(str::trim)
+          .filter(|p| !p.is_empty());
+        let answer_regex = match pattern {
+          _ if !parts.is_empty() && !answers.is_empty() => {
+            Err("Cannot start: alternative answers can't be combined with blanks.".to_string())
+          }
+          Some(_) if !parts.is_empty() => {
+            Err("Cannot start: an answer pattern can't be combined with blanks.".to_string())
+          }
+          Some(p) => compile_answer_regex(p).map(Some),
+          None => Ok(None),
+        };
+        let answer_regex = match answer_regex {
+          Ok(re) => re,
+          Err(msg) => {
+            let _ = self.tx.send(InternalMsg::Toast {
+              to_user: 0,
+              msg,
+              kind: "error".into(),
+            });
+            return;
+          }
+        };
+        let answer = if answer.is_empty() && !parts.is_empty() {
+          parts.join(" / ")
+        } else if let Some(p) = pattern.filter(|_| answer.is_empty()) {
+          format!("/{}/", p)
+        } else {
+          answer
+        };
         let mut game = ChainGame::new(problem, answer, hint);
+        game.answer_parts = parts;
+        game.match_strategy = options.match_strategy.unwrap_or(if self.strict_match {
+          MatchStrategy::Exact
+        } else {
+          MatchStrategy::Normalized
+        });
+        game.answer_regex = answer_regex;
+        game.alt_answers = answers;
+        game.hint_stages = hint_stages;
+        game.turn_toast = self.turn_toasts;
+        game.start_delay = Duration::from_secs(self.start_countdown_secs);
+        game.answer_hint_interval = options
+          .answer_hint_interval_secs
+          .filter(|s| *s > 0)
+          .map(Duration::from_secs);
+        game.blind = options.blind;
+        game.hide_length = options.hide_length;
+        game.last_pick_choice = options.last_pick_choice;
+        if let Some(ms) = options.min_take_interval_ms {
+          game.min_take_interval = Duration::from_millis(ms);
+        }
+        if let Some(secs) = timers.pick_secs {
+          game.pick_time = Duration::from_secs(secs);
+        }
+        if let Some(secs) = timers.answer_secs {
+          game.answer_time = Duration::from_secs(secs);
+        }
+        game.shuffle_order = shuffle;
         game.setup_players(active_players);
         game.start(&self.tx);
-        self.session = GameSession::Chain(game);
+        self.session = GameSession::Chain(Box::new(game));
       }
       RoomType::Pinyin => {
-        let mut game = PinyinGame::new(answer, hint, pinyin_table);
+        if let Some(n) = options.describer_count
+          && (n < 1 || n >= active_players.len())
+        {
+          let _ = self.tx.send(InternalMsg::Toast {
+            to_user: 0,
+            msg: format!(
+              "Cannot start: describer count must be between 1 and {}.",
+              active_players.len().saturating_sub(1)
+            ),
+            kind: "error".into(),
+          });
+          return;
+        }
+        let mut game = PinyinGame::new(answer, hint, pinyin_tables.pick(self.tone_sensitive));
+        game.tone_sensitive = self.tone_sensitive;
+        game.alt_answers = answers;
+        game.hint_stages = hint_stages;
+        game.turn_toast = self.turn_toasts;
+        game.describer_count = options.describer_count;
+        if let Some(limit) = options.skip_limit {
+          game.skip_limit = limit;
+        }
+        if let Some(full) = options.guesser_sees_full_chain {
+          game.guesser_sees_full_chain = full;
+        }
+        if let Some(secs) = timers.describer_secs {
+          game.turn_time = Duration::from_secs(secs);
+        }
+        game.shuffle_order = shuffle;
         game.setup_players(active_players);
         game.start(&self.tx);
-        self.session = GameSession::Pinyin(game);
+        self.session = GameSession::Pinyin(Box::new(game));
       }
     }
+    for p in self.players.values_mut() {
+      p.ready = false;
+    }
+    self.paused_at = None;
     let _ = self.tx.send(InternalMsg::StateUpdated);
   }
 
+  /// Show the whole board and every answer to all viewers, spectators included.
+  /// Only allowed once the game has settled.
+  pub fn reveal_all(&mut self) -> Result<(), String> {
+    if self.phase() != GamePhase::Settlement {
+      return Err("Game has not finished".to_string());
+    }
+    match &mut self.session {
+      GameSession::Chain(g) => g.force_reveal = true,
+      GameSession::Pinyin(g) => g.force_reveal = true,
+      GameSession::None => {}
+    }
+    let _ = self.tx.send(InternalMsg::Log {
+      who: "System".into(),
+      text: "An admin revealed the full board".into(),
+      time: chrono::Local::now().format("%H:%M:%S").to_string(),
+    });
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+    Ok(())
+  }
+
+  pub fn force_finish(&mut self) -> Result<(), String> {
+    match &mut self.session {
+      GameSession::Chain(g) => g.force_finish(&self.tx)?,
+      GameSession::Pinyin(g) => g.force_finish(&self.tx)?,
+      GameSession::None => return Err("No game in progress".to_string()),
+    }
+    self.paused_at = None;
+    let _ = self.tx.send(InternalMsg::Log {
+      who: "System".into(),
+      text: "An admin ended the game early".into(),
+      time: chrono::Local::now().format("%H:%M:%S").to_string(),
+    });
+    Ok(())
+  }
+
+  /// Freeze the running game's timers until `resume`.
+  pub fn pause(&mut self) -> Result<(), String> {
+    if !self.is_game_in_progress() {
+      return Err("No game in progress".to_string());
+    }
+    if self.paused_at.is_some() {
+      return Err("Game is already paused".to_string());
+    }
+    self.paused_at = Some(Instant::now());
+    self.log("An admin paused the game".into());
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+    Ok(())
+  }
+
+  pub fn resume(&mut self) -> Result<(), String> {
+    let Some(at) = self.paused_at.take() else {
+      return Err("Game is not paused".to_string());
+    };
+    let paused = at.elapsed();
+    match &mut self.session {
+      GameSession::Chain(g) => g.shift_deadlines(paused),
+      GameSession::Pinyin(g) => g.shift_deadlines(paused),
+      GameSession::None => {}
+    }
+    // Time away during the pause doesn't count against a reserved seat
+    for p in self.players.values_mut().filter(|p| !p.is_online) {
+      p.last_seen += paused;
+    }
+    self.log("An admin resumed the game".into());
+    let _ = self.tx.send(InternalMsg::StateUpdated);
+    Ok(())
+  }
+
   pub fn stop_game(&mut self) {
     self.session = GameSession::None;
+    self.paused_at = None;
+    self.chain_match = None;
     self.kick_offline_players();
     let _ = self.tx.send(InternalMsg::StateUpdated);
   }
 
+  /// `get_view` rendered by `render`, shared with every connection that sees the
+  /// same thing until the next state update or until it is `VIEW_MAX_AGE` old.
+  pub fn cached_view(
+    &self,
+    user_id: i64,
+    is_site_super: bool,
+    render: impl FnOnce(&ClientView) -> Bytes,
+  ) -> Bytes {
+    let is_admin = is_site_super || self.admin_ids.contains(&user_id);
+    // Non-admin spectators never see themselves in the list, so only seated
+    // ones (who may still have game data) need their own view
+    let key = match self.players.get(&user_id) {
+      Some(p) if p.is_spectator && !is_admin && !self.is_seated(user_id) => ViewKey::Spectator,
+      _ => ViewKey::Viewer(user_id, is_admin),
+    };
+
+    let mut cache = self.view_cache.lock().unwrap_or_else(|e| e.into_inner());
+    let mut stale = false;
+    loop {
+      match cache.rx.try_recv() {
+        Ok(InternalMsg::StateUpdated) | Err(broadcast::error::TryRecvError::Lagged(_)) => {
+          stale = true
+        }
+        Ok(_) => {}
+        Err(_) => break,
+      }
+    }
+    if stale {
+      cache.frames.clear();
+    }
+    let now = Instant::now();
+    if let Some((at, frame)) = cache.frames.get(&key)
+      && now.duration_since(*at) < VIEW_MAX_AGE
+    {
+      return frame.clone();
+    }
+    let frame = render(&self.get_view(Some(user_id), is_site_super));
+    cache.frames.insert(key, (now, frame.clone()));
+    frame
+  }
+
+  fn is_seated(&self, user_id: i64) -> bool {
+    match &self.session {
+      GameSession::None => false,
+      GameSession::Chain(g) => g.players.contains(&user_id),
+      GameSession::Pinyin(g) => g.seating_order().contains(&user_id),
+    }
+  }
+
   pub fn get_view(&self, user_id: Option<i64>, is_site_super: bool) -> ClientView {
     let is_admin = user_id
       .map(|id| self.admin_ids.contains(&id))
       .unwrap_or(false)
       || is_site_super;
 
-    let is_spectator = user_id.map_or(false, |id| {
-      self.players.get(&id).map_or(false, |p| p.is_spectator)
-    });
+    let is_spectator =
+      user_id.is_some_and(|id| self.players.get(&id).is_some_and(|p| p.is_spectator));
+    // Spectators see everything if they're admins or the room lets every spectator
+    let super_view = is_spectator && (is_admin || self.spectator_vision == SpectatorVision::Full);
 
     // 实时颜色计算逻辑
     // 1. 确定排序依据（游戏中用游戏列表，大厅中用 ID 排序）
     let active_order: Vec<i64> = match &self.session {
       GameSession::Chain(g) => g.players.clone(),
-      GameSession::Pinyin(g) => g.players.clone(),
+      GameSession::Pinyin(g) => g.seating_order(),
       GameSession::None => {
-        let mut ids: Vec<i64> = self
+        let ids: Vec<i64> = self
           .players
           .iter()
           .filter(|(_, p)| !p.is_spectator)
           .map(|(k, _)| *k)
           .collect();
-        ids.sort(); // 稳定排序
-        ids
+        // 有预排座次时按预排顺序，否则稳定排序
+        seat_in_order(&self.planned_order, ids)
       }
     };
 
@@ -318,18 +1514,32 @@ impl Room {
       hue_map.insert(*pid, hue);
     }
 
-    let (phase, hint, deadline, grid, pinyin_state, winner, correct_ans) = match &self.session {
-      GameSession::None => (
-        GamePhase::Waiting,
-        String::new(),
-        None,
-        None,
-        None,
-        None,
-        None,
-      ),
-      GameSession::Chain(g) => g.get_view_data(user_id, is_spectator && is_admin, &hue_map),
-      GameSession::Pinyin(g) => g.get_view_data(user_id, is_spectator && is_admin, &hue_map),
+    let data = match &self.session {
+      GameSession::None => GameViewData {
+        phase: GamePhase::Waiting,
+        hint: String::new(),
+        deadline: None,
+        grid: None,
+        pinyin_state: None,
+        winner: None,
+        correct_answer: None,
+        my_draft: None,
+        can_act: false,
+        can_answer: false,
+        pending_submissions: None,
+        submit_ms: None,
+        takes: None,
+        answer_parts: None,
+        part_results: None,
+        match_strategy: None,
+        final_pick: false,
+        can_undo: false,
+        remaining_chars: None,
+        starting: false,
+        game_duration_ms: None,
+      },
+      GameSession::Chain(g) => g.get_view_data(user_id, super_view, &hue_map),
+      GameSession::Pinyin(g) => g.get_view_data(user_id, super_view, &hue_map),
     };
 
     let mut player_views = Vec::new();
@@ -340,7 +1550,8 @@ impl Room {
     // If gaming, put game order first
     let game_order = match &self.session {
       GameSession::Chain(g) => Some(g.players.clone()),
-      GameSession::Pinyin(g) => Some(g.players.clone()),
+      GameSession::Pinyin(g) => Some(g.seating_order()),
+      GameSession::None if !self.planned_order.is_empty() => Some(active_order.clone()),
       GameSession::None => None,
     };
 
@@ -353,7 +1564,7 @@ impl Room {
           pid,
           user_id,
           is_admin,
-          is_spectator,
+          super_view,
           &hue_map,
           &mut player_views,
         );
@@ -367,7 +1578,7 @@ impl Room {
         pid,
         user_id,
         is_admin,
-        is_spectator,
+        super_view,
         &hue_map,
         &mut player_views,
       );
@@ -377,21 +1588,86 @@ impl Room {
       room_id: self.id.to_string(),
       room_name: self.name.clone(),
       room_type: self.room_type,
-      phase,
-      hint,
-      deadline_ms: deadline.map(|t| t.saturating_duration_since(Instant::now()).as_millis() as u64),
+      phase: data.phase,
+      hint: data.hint,
+      // While paused, the time that was left when the pause began
+      deadline_ms: data.deadline.map(|t| {
+        t.saturating_duration_since(self.paused_at.unwrap_or_else(Instant::now))
+          .as_millis() as u64
+      }),
+      server_now_ms: chrono::Utc::now().timestamp_millis() as u64,
       is_admin,
       admin_ids: if is_admin {
         Some(self.admin_ids.iter().cloned().collect())
       } else {
         None
       },
+      banned: is_admin.then(|| {
+        let mut banned: Vec<BannedUser> = self
+          .banned
+          .iter()
+          .map(|(id, name)| BannedUser {
+            id: *id,
+            name: name.clone(),
+          })
+          .collect();
+        banned.sort_by(|a, b| a.name.cmp(&b.name));
+        banned
+      }),
+      default_timers: is_admin.then_some(self.default_timers),
+      disconnect_policy: is_admin.then_some(self.disconnect_policy),
+      turn_toasts: is_admin.then_some(self.turn_toasts),
+      start_countdown_secs: is_admin.then_some(self.start_countdown_secs),
+      spectator_vision: is_admin.then_some(self.spectator_vision),
+      strict_match: is_admin.then_some(self.strict_match),
+      tone_sensitive: is_admin.then_some(self.tone_sensitive),
+      require_ready: is_admin.then_some(self.require_ready),
+      chain_match: self.chain_match.as_ref().map(|m| m.view()),
+      leaderboard: match &self.session {
+        GameSession::Chain(g) if g.phase == GamePhase::Settlement => {
+          Some(g.leaderboard(&self.players))
+        }
+        _ => None,
+      },
+      paused: self.paused_at.is_some(),
+      pending_game: self
+        .pending_game
+        .as_ref()
+        .filter(|_| is_admin)
+        .map(|s| PendingConfig {
+          problem_len: s.problem.graphemes(true).count(),
+          answer_len: s.answer.chars().count(),
+          has_hint: !s.hint.is_empty() || !s.options.hint_stages.is_empty(),
+          options: s.options.clone(),
+        }),
       players: player_views,
       max_players: self.max_players,
-      grid,
-      pinyin_state,
-      winner,
-      correct_answer: correct_ans,
+      max_spectators: self.max_spectators,
+      cell_size_px: self.cell_size_px,
+      grid: data.grid,
+      pinyin_state: data.pinyin_state,
+      winner: data.winner,
+      correct_answer: data.correct_answer,
+      my_draft: data.my_draft,
+      can_act: data.can_act && !is_spectator && self.paused_at.is_none(),
+      can_answer: data.can_answer && !is_spectator && self.paused_at.is_none(),
+      pending_submissions: data.pending_submissions,
+      submit_ms: data.submit_ms,
+      takes: data.takes,
+      answer_parts: data.answer_parts,
+      part_results: data.part_results,
+      match_strategy: data.match_strategy,
+      final_pick: data.final_pick,
+      can_undo: data.can_undo && !is_spectator && self.paused_at.is_none(),
+      remaining_chars: data.remaining_chars,
+      starting: data.starting,
+      game_duration_ms: data.game_duration_ms,
+      turn_times: match &self.session {
+        GameSession::Pinyin(g) if g.phase == GamePhase::Settlement => {
+          Some(g.turn_times(&self.players))
+        }
+        _ => None,
+      },
     }
   }
 
@@ -400,18 +1676,14 @@ impl Room {
     pid: i64,
     user_id: Option<i64>,
     is_viewer_admin: bool,
-    is_viewer_spectator: bool,
+    super_view: bool,
     hue_map: &HashMap<i64, u16>,
     views: &mut Vec<PlayerView>,
   ) {
     if let Some(rp) = self.players.get(&pid) {
       let (status, score, active, ans) = match &self.session {
-        GameSession::Chain(g) => {
-          g.get_player_state(pid, user_id, is_viewer_admin && is_viewer_spectator)
-        }
-        GameSession::Pinyin(g) => {
-          g.get_player_state(pid, user_id, is_viewer_admin && is_viewer_spectator)
-        }
+        GameSession::Chain(g) => g.get_player_state(pid, user_id, super_view),
+        GameSession::Pinyin(g) => g.get_player_state(pid, user_id, super_view),
         GameSession::None => (PlayerStatus::Waiting, None, false, None),
       };
 
@@ -432,12 +1704,676 @@ impl Room {
         },
         is_me: user_id == Some(pid),
         is_online: rp.is_online,
+        disconnect_remaining_ms: if !rp.is_online && !rp.is_spectator && self.is_game_in_progress()
+        {
+          Some(
+            self
+              .disconnect_policy
+              .grace()
+              .saturating_sub(rp.last_seen.elapsed())
+              .as_millis() as u64,
+          )
+        } else {
+          None
+        },
         is_active_turn: active,
-        score_display: score,
+        // A match shows round wins next to this game's score
+        score_display: match &self.chain_match {
+          Some(m) if !rp.is_spectator => Some(match score {
+            Some(s) => format!("{} · {}W", s, m.wins_of(pid)),
+            None => format!("{}W", m.wins_of(pid)),
+          }),
+          _ => score,
+        },
         answer: ans,
+        submitted: matches!(&self.session, GameSession::Chain(g) if g.has_submitted(pid)),
+        ready: rp.ready && !self.is_game_in_progress(),
         is_spectator: rp.is_spectator,
         is_admin: rp.is_admin,
       });
     }
   }
 }
+
+/// Drop control characters (newlines included), trim the ends and cap the length.
+/// Spaces inside the answer are kept as they may be meaningful.
+fn sanitize_answer(content: &str, max_len: usize) -> String {
+  let cleaned: String = content.chars().filter(|c| !c.is_control()).collect();
+  cleaned
+    .trim()
+    .chars()
+    .take(max_len)
+    .collect::<String>()
+    .trim_end()
+    .to_string()
+}
+
+/// Keep `order` for the players still present and seat everyone else after them by id.
+fn seat_in_order(order: &[i64], present: Vec<i64>) -> Vec<i64> {
+  let mut seated: Vec<i64> = order
+    .iter()
+    .copied()
+    .filter(|pid| present.contains(pid))
+    .collect();
+  let mut newcomers: Vec<i64> = present
+    .into_iter()
+    .filter(|pid| !seated.contains(pid))
+    .collect();
+  newcomers.sort();
+  seated.extend(newcomers);
+  seated
+}
+
+/// Check every set timer against its accepted range.
+pub fn validate_timers(timers: &Timers) -> Result<(), String> {
+  let checks = [
+    ("Pick time", timers.pick_secs, &PICK_SECS),
+    ("Answer time", timers.answer_secs, &ANSWER_SECS),
+    ("Describer time", timers.describer_secs, &DESCRIBER_SECS),
+  ];
+  for (label, secs, range) in checks {
+    if let Some(secs) = secs
+      && !range.contains(&secs)
+    {
+      return Err(format!(
+        "{} must be between {} and {} seconds",
+        label,
+        range.start(),
+        range.end()
+      ));
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::pinyin_utils::PinyinTable;
+
+  const ADMIN: i64 = 1;
+
+  fn room(rtype: RoomType) -> Room {
+    Room::new(
+      Uuid::nil(),
+      "test".into(),
+      rtype,
+      4,
+      ADMIN,
+      Timers::default(),
+      &Config::for_tests(),
+    )
+  }
+
+  fn join(room: &mut Room, id: i64, spectate: bool) -> Result<Uuid, String> {
+    room
+      .join(id, format!("p{}", id), spectate, false, None)
+      .map(|(_, token)| token)
+  }
+
+  fn setup(problem: &str, answer: &str) -> GameSetup {
+    GameSetup {
+      problem: problem.into(),
+      answer: answer.into(),
+      answers: vec![],
+      hint: String::new(),
+      options: GameOptions::default(),
+    }
+  }
+
+  fn tables() -> PinyinTables {
+    PinyinTables::new(PinyinTable::new())
+  }
+
+  #[test]
+  fn dropped_spectator_frees_their_slot() {
+    let mut room = room(RoomType::Chain);
+    room.max_spectators = Some(1);
+    let token = join(&mut room, 2, true).unwrap();
+    assert!(join(&mut room, 3, true).is_err());
+    room.leave(2, token);
+    assert!(join(&mut room, 3, true).is_ok());
+  }
+
+  #[test]
+  fn dropped_player_is_offline_from_the_drop() {
+    let mut room = room(RoomType::Chain);
+    join(&mut room, ADMIN, false).unwrap();
+    let token = join(&mut room, 2, false).unwrap();
+    room.start_game(setup("abcdef", "x"), tables());
+    assert!(room.is_game_in_progress());
+
+    room.leave(2, token);
+    let dropped_at = room.players[&2].last_seen;
+    assert!(!room.players[&2].is_online);
+
+    // The window lapses without a reconnect
+    room.players.get_mut(&2).unwrap().resume_until = Some(Instant::now());
+    std::thread::sleep(Duration::from_millis(5));
+    room.tick(&broadcast::channel(4).0);
+    let p = &room.players[&2];
+    assert!(!p.is_online && p.resume_until.is_none());
+    assert_eq!(p.last_seen, dropped_at);
+  }
+
+  #[test]
+  fn resume_within_the_window_restores_the_seat() {
+    let mut room = room(RoomType::Chain);
+    join(&mut room, ADMIN, false).unwrap();
+    let token = join(&mut room, 2, false).unwrap();
+    room.start_game(setup("abcdef", "x"), tables());
+    room.leave(2, token);
+    room
+      .join(2, "p2".into(), false, false, Some(token))
+      .unwrap();
+    let p = &room.players[&2];
+    assert!(p.is_online && p.resume_until.is_none());
+  }
+
+  #[test]
+  fn max_players_rejects_shrinking_below_seated() {
+    let mut room = room(RoomType::Chain);
+    join(&mut room, ADMIN, false).unwrap();
+    join(&mut room, 2, false).unwrap();
+    join(&mut room, 3, true).unwrap();
+    assert!(room.set_max_players(1).is_err());
+    assert_eq!(room.max_players, 4);
+    // Spectators don't hold seats
+    assert!(room.set_max_players(2).is_ok());
+  }
+
+  #[test]
+  fn max_players_counts_offline_players() {
+    let mut room = room(RoomType::Chain);
+    join(&mut room, ADMIN, false).unwrap();
+    let token = join(&mut room, 2, false).unwrap();
+    room.start_game(setup("abcdef", "x"), tables());
+    room.leave(2, token);
+    assert!(room.set_max_players(1).is_err());
+  }
+
+  #[test]
+  fn max_players_can_always_grow() {
+    let mut room = room(RoomType::Chain);
+    join(&mut room, ADMIN, false).unwrap();
+    join(&mut room, 2, false).unwrap();
+    room.start_game(setup("abcdef", "x"), tables());
+    assert!(room.set_max_players(8).is_ok());
+    assert_eq!(room.max_players, 8);
+    assert_eq!(room.players.len(), 2);
+  }
+
+  #[test]
+  fn max_players_rejects_zero() {
+    let mut room = room(RoomType::Chain);
+    assert!(room.set_max_players(0).is_err());
+    assert_eq!(room.max_players, 4);
+  }
+
+  #[test]
+  fn stale_connection_close_is_ignored() {
+    let mut room = room(RoomType::Chain);
+    let old = join(&mut room, 2, false).unwrap();
+    join(&mut room, 2, false).unwrap();
+    room.leave(2, old);
+    assert!(room.players[&2].is_online);
+  }
+
+  #[test]
+  fn server_timers_fill_what_the_creator_left_unset() {
+    let mut config = Config::for_tests();
+    config.default_timers = Timers {
+      pick_secs: Some(20),
+      answer_secs: Some(90),
+      describer_secs: None,
+    };
+    let own = Timers {
+      pick_secs: Some(10),
+      ..Timers::default()
+    };
+    let room = Room::new(
+      Uuid::nil(),
+      "test".into(),
+      RoomType::Chain,
+      4,
+      ADMIN,
+      own,
+      &config,
+    );
+    assert_eq!(
+      room.default_timers,
+      Timers {
+        pick_secs: Some(10),
+        answer_secs: Some(90),
+        describer_secs: None,
+      }
+    );
+  }
+
+  /// Render `user_id`'s view through the cache, counting the renders it needs.
+  fn view_for(room: &Room, user_id: i64, renders: &mut usize) -> Bytes {
+    room.cached_view(user_id, false, |_| {
+      *renders += 1;
+      Bytes::from_static(b"view")
+    })
+  }
+
+  #[test]
+  fn spectators_share_one_render() {
+    let mut room = room(RoomType::Chain);
+    join(&mut room, 2, false).unwrap();
+    join(&mut room, 3, false).unwrap();
+    let spectators: Vec<i64> = (100..150).collect();
+    for &id in &spectators {
+      join(&mut room, id, true).unwrap();
+    }
+    room.start_game(setup("abcdef", "answer"), tables());
+    let _ = room.tx.send(InternalMsg::StateUpdated);
+
+    let mut renders = 0;
+    for &id in &spectators {
+      view_for(&room, id, &mut renders);
+    }
+    assert_eq!(renders, 1);
+    // Seated players each see their own
+    view_for(&room, 2, &mut renders);
+    view_for(&room, 3, &mut renders);
+    view_for(&room, 2, &mut renders);
+    assert_eq!(renders, 3);
+
+    // The next update throws every frame away
+    let _ = room.tx.send(InternalMsg::StateUpdated);
+    for &id in &spectators {
+      view_for(&room, id, &mut renders);
+    }
+    assert_eq!(renders, 4);
+  }
+
+  #[test]
+  fn old_frames_are_rendered_again() {
+    let mut room = room(RoomType::Chain);
+    join(&mut room, 100, true).unwrap();
+    let mut renders = 0;
+    view_for(&room, 100, &mut renders);
+    view_for(&room, 100, &mut renders);
+    assert_eq!(renders, 1);
+    for (at, _) in room.view_cache.lock().unwrap().frames.values_mut() {
+      *at -= VIEW_MAX_AGE;
+    }
+    view_for(&room, 100, &mut renders);
+    assert_eq!(renders, 2);
+  }
+
+  /// A chain game between `ADMIN` and player 2, still picking.
+  fn picking() -> Room {
+    let mut room = room(RoomType::Chain);
+    join(&mut room, ADMIN, false).unwrap();
+    join(&mut room, 2, false).unwrap();
+    room.start_game(setup("abcdef", "answer"), tables());
+    room
+  }
+
+  #[test]
+  fn spectators_join_mid_game() {
+    let mut room = picking();
+    assert!(matches!(&room.session, GameSession::Chain(g) if g.phase == GamePhase::Picking));
+    assert!(join(&mut room, 3, true).is_ok());
+    assert!(room.players[&3].is_spectator);
+
+    let view = room.get_view(Some(3), false);
+    assert_eq!(view.phase, GamePhase::Picking);
+    assert!(!view.can_act && !view.can_answer);
+    assert_eq!(view.correct_answer, None);
+    let grid = view.grid.unwrap();
+    assert_eq!(grid.len(), 6);
+    assert!(grid.iter().all(|c| c.char_content.is_none()));
+  }
+
+  #[test]
+  fn only_players_are_kept_out_mid_game() {
+    let mut room = picking();
+    assert_eq!(
+      join(&mut room, 3, false),
+      Err("Game is in progress".to_string())
+    );
+    // Watching first doesn't get around it
+    join(&mut room, 3, true).unwrap();
+    assert_eq!(
+      join(&mut room, 3, false),
+      Err("Game is in progress".to_string())
+    );
+    assert!(room.players[&3].is_spectator);
+  }
+
+  /// A chain room with `ADMIN` and player 2 seated, limited to `problem` and
+  /// `answer` lengths.
+  fn limited(problem: usize, answer: usize) -> Room {
+    let mut room = room(RoomType::Chain);
+    room.max_problem_len = problem;
+    room.max_answer_len = answer;
+    join(&mut room, ADMIN, false).unwrap();
+    join(&mut room, 2, false).unwrap();
+    room
+  }
+
+  /// The first error toast sent so far on `rx`.
+  fn error_toast(rx: &mut broadcast::Receiver<InternalMsg>) -> Option<String> {
+    while let Ok(msg) = rx.try_recv() {
+      if let InternalMsg::Toast { msg, kind, .. } = msg
+        && kind == "error"
+      {
+        return Some(msg);
+      }
+    }
+    None
+  }
+
+  #[test]
+  fn problem_length_counts_graphemes_up_to_the_limit() {
+    let mut room = limited(3, 128);
+    // "e" plus a combining accent is one grapheme but two chars
+    room.start_game(setup("ae\u{301}c", "x"), tables());
+    assert!(room.is_game_in_progress());
+
+    let mut room = limited(3, 128);
+    let mut rx = room.tx.subscribe();
+    room.start_game(setup("abcd", "x"), tables());
+    assert!(!room.is_game_in_progress());
+    assert!(error_toast(&mut rx).unwrap().contains("max 3"));
+  }
+
+  #[test]
+  fn answer_length_counts_chars_up_to_the_limit() {
+    let mut room = limited(500, 4);
+    room.start_game(setup("abcdef", "北京大学"), tables());
+    assert!(room.is_game_in_progress());
+
+    let mut room = limited(500, 4);
+    let mut rx = room.tx.subscribe();
+    let mut too_long = setup("abcdef", "abcd");
+    too_long.answers = vec!["abcde".into()];
+    room.start_game(too_long, tables());
+    assert!(!room.is_game_in_progress());
+    assert!(error_toast(&mut rx).unwrap().contains("longer than 4"));
+  }
+
+  /// Put a running chain game straight into its answer window.
+  fn open_answers(room: &mut Room) -> &mut ChainGame {
+    let GameSession::Chain(g) = &mut room.session else {
+      panic!("not a chain game");
+    };
+    g.phase = GamePhase::Answering;
+    g
+  }
+
+  fn chain_answer(room: &Room, pid: i64) -> Option<String> {
+    match &room.session {
+      GameSession::Chain(g) => g.player_data[&pid].answer.clone(),
+      _ => None,
+    }
+  }
+
+  #[test]
+  fn answers_lose_control_chars_and_outer_whitespace() {
+    let mut room = limited(500, 5);
+    room.start_game(setup("abcdef", "x"), tables());
+    open_answers(&mut room);
+    room.handle_answer(2, " a\0b\n c\t ".into());
+    assert_eq!(chain_answer(&room, 2).as_deref(), Some("ab c"));
+    // Capped to the answer limit, with no space left dangling at the cut
+    room.handle_answer(ADMIN, "abcd efgh".into());
+    assert_eq!(chain_answer(&room, ADMIN).as_deref(), Some("abcd"));
+  }
+
+  #[test]
+  fn multi_part_answers_keep_one_line_per_blank() {
+    let mut room = limited(500, 128);
+    room.start_game(setup("abcdef", "x"), tables());
+    open_answers(&mut room).answer_parts = vec!["a".into(), "b".into()];
+    room.handle_answer(2, "a\0 \n b\r\nextra".into());
+    assert_eq!(chain_answer(&room, 2).as_deref(), Some("a\nb"));
+  }
+
+  #[test]
+  fn mid_game_shrink_stops_at_the_table_and_binds_later_joins() {
+    let mut room = picking();
+    join(&mut room, 3, true).unwrap();
+    assert_eq!(
+      room.set_max_players(1),
+      Err("Cannot shrink below the 2 players already seated".to_string())
+    );
+    assert!(room.set_max_players(2).is_ok());
+    room.stop_game();
+    assert_eq!(join(&mut room, 3, false), Err("Room is full".to_string()));
+  }
+
+  /// Drop `pid`'s connection and let its resume window run out.
+  fn disconnect(room: &mut Room, pid: i64, token: Uuid) {
+    room.leave(pid, token);
+    room.players.get_mut(&pid).unwrap().resume_until = Some(Instant::now());
+    room.tick(&broadcast::channel(4).0);
+  }
+
+  fn picker(room: &Room) -> i64 {
+    match &room.session {
+      GameSession::Chain(g) => g.players[g.current_turn_idx],
+      _ => panic!("not a chain game"),
+    }
+  }
+
+  #[test]
+  fn free_immediately_unseats_and_moves_on() {
+    let mut room = room(RoomType::Chain);
+    room.disconnect_policy = DisconnectPolicy::FreeImmediately;
+    room.start_countdown_secs = 0;
+    let tokens = HashMap::from([
+      (ADMIN, join(&mut room, ADMIN, false).unwrap()),
+      (2, join(&mut room, 2, false).unwrap()),
+    ]);
+    room.start_game(setup("abcdef", "x"), tables());
+    let gone = picker(&room);
+    let stays = if gone == ADMIN { 2 } else { ADMIN };
+    disconnect(&mut room, gone, tokens[&gone]);
+
+    assert!(!room.players.contains_key(&gone));
+    let GameSession::Chain(g) = &room.session else {
+      panic!("not a chain game");
+    };
+    // The one left took the rest and is answering
+    assert_eq!(g.phase, GamePhase::Answering);
+    assert_eq!(g.player_data[&stays].obtained_indices.len(), 6);
+    // A substitute can take the seat once the game is over
+    room.stop_game();
+    assert!(join(&mut room, 3, false).is_ok());
+  }
+
+  #[test]
+  fn reserve_keeps_the_seat_until_the_grace_runs_out() {
+    let mut room = room(RoomType::Chain);
+    room.disconnect_policy = DisconnectPolicy::Reserve { secs: 60 };
+    room.start_countdown_secs = 0;
+    join(&mut room, ADMIN, false).unwrap();
+    let token = join(&mut room, 2, false).unwrap();
+    room.start_game(setup("abcdef", "x"), tables());
+    open_answers(&mut room);
+    disconnect(&mut room, 2, token);
+
+    let submitted = |room: &Room| match &room.session {
+      GameSession::Chain(g) => g.player_data[&2].status == PlayerStatus::Submitted,
+      _ => false,
+    };
+    assert!(room.players.contains_key(&2) && !room.players[&2].is_online);
+    assert!(!submitted(&room));
+
+    room.players.get_mut(&2).unwrap().last_seen -= Duration::from_secs(61);
+    room.tick(&broadcast::channel(4).0);
+    assert!(submitted(&room));
+    assert!(room.players.contains_key(&2));
+  }
+
+  /// A chain game where the first picker took a cell and then answered,
+  /// watched by spectator 3.
+  fn watched(vision: SpectatorVision) -> (Room, i64) {
+    let mut room = room(RoomType::Chain);
+    room.spectator_vision = vision;
+    room.start_countdown_secs = 0;
+    join(&mut room, ADMIN, false).unwrap();
+    join(&mut room, 2, false).unwrap();
+    join(&mut room, 3, true).unwrap();
+    room.start_game(setup("abcdef", "answer"), tables());
+    let first = picker(&room);
+    room.handle_action(first, "take".into());
+    open_answers(&mut room);
+    room.handle_answer(first, "guess".into());
+    (room, first)
+  }
+
+  #[test]
+  fn neutral_spectators_see_colors_only() {
+    let (room, first) = watched(SpectatorVision::Neutral);
+    let view = room.get_view(Some(3), false);
+    let grid = view.grid.unwrap();
+    assert!(grid[0].owner_color_hue.is_some());
+    assert!(grid.iter().all(|c| c.char_content.is_none()));
+    assert_eq!(view.correct_answer, None);
+    let player = view.players.iter().find(|p| p.id == first).unwrap();
+    assert!(player.submitted);
+    assert_eq!(player.answer, None);
+  }
+
+  #[test]
+  fn full_spectators_see_what_admins_see() {
+    let (room, first) = watched(SpectatorVision::Full);
+    let view = room.get_view(Some(3), false);
+    let grid = view.grid.unwrap();
+    assert!(grid.iter().all(|c| c.char_content.is_some()));
+    assert_eq!(view.correct_answer.as_deref(), Some("answer"));
+    let player = view.players.iter().find(|p| p.id == first).unwrap();
+    assert_eq!(player.answer.as_deref(), Some("guess"));
+    // Players themselves still only see their own cells
+    let other = if first == ADMIN { 2 } else { ADMIN };
+    let grid = room.get_view(Some(other), false).grid.unwrap();
+    assert!(grid.iter().all(|c| c.char_content.is_none()));
+  }
+
+  #[test]
+  fn strict_match_turns_fuzzy_matching_off() {
+    for (strict, accepted) in [(false, true), (true, false)] {
+      let mut room = limited(500, 128);
+      room.strict_match = strict;
+      room.start_game(setup("abcdef", "2024 Answer"), tables());
+      open_answers(&mut room);
+      room.handle_answer(2, "２０２４　 answer".into());
+      let GameSession::Chain(g) = &room.session else {
+        panic!("not a chain game");
+      };
+      assert_eq!(g.points(2) > 0, accepted, "strict_match {}", strict);
+    }
+  }
+
+  #[test]
+  fn oversized_answers_are_refused_and_never_stored() {
+    let mut room = limited(500, 128);
+    room.max_message_bytes = 16;
+    room.start_game(setup("abcdef", "x"), tables());
+    open_answers(&mut room);
+    let mut rx = room.tx.subscribe();
+    // Over the limit in bytes even though it is only six chars
+    room.handle_answer(2, "北京大学北京".into());
+    assert!(error_toast(&mut rx).unwrap().contains("over 16 bytes"));
+    assert_eq!(chain_answer(&room, 2), None);
+    // The player can still answer within the limit
+    room.handle_answer(2, "北京大学北".into());
+    assert_eq!(chain_answer(&room, 2).as_deref(), Some("北京大学北"));
+  }
+
+  #[test]
+  fn oversized_chat_is_refused() {
+    let mut room = room(RoomType::Chain);
+    room.max_message_bytes = 16;
+    join(&mut room, 2, false).unwrap();
+    let mut rx = room.tx.subscribe();
+    room.handle_chat(2, "x".repeat(17));
+    let mut chats = 0;
+    let mut refused = false;
+    while let Ok(msg) = rx.try_recv() {
+      match msg {
+        InternalMsg::Chat { .. } => chats += 1,
+        InternalMsg::Toast { to_user, kind, .. } => refused |= to_user == 2 && kind == "error",
+        _ => {}
+      }
+    }
+    assert!(refused);
+    assert_eq!(chats, 0);
+  }
+
+  #[test]
+  fn picker_refreshing_keeps_the_turn() {
+    let mut room = room(RoomType::Chain);
+    room.start_countdown_secs = 0;
+    let tokens = HashMap::from([
+      (ADMIN, join(&mut room, ADMIN, false).unwrap()),
+      (2, join(&mut room, 2, false).unwrap()),
+    ]);
+    room.start_game(setup("abcdef", "x"), tables());
+    let first = picker(&room);
+    room.leave(first, tokens[&first]);
+    room.tick(&broadcast::channel(4).0);
+    assert_eq!(picker(&room), first);
+    let GameSession::Chain(g) = &room.session else {
+      panic!("not a chain game");
+    };
+    assert_eq!(g.player_data[&first].status, PlayerStatus::Picking);
+
+    room
+      .join(
+        first,
+        format!("p{}", first),
+        false,
+        false,
+        Some(tokens[&first]),
+      )
+      .unwrap();
+    assert!(room.players[&first].is_online);
+    room.handle_action(first, "take".into());
+    let GameSession::Chain(g) = &room.session else {
+      panic!("not a chain game");
+    };
+    assert_eq!(g.player_data[&first].obtained_indices, vec![0]);
+  }
+
+  #[test]
+  fn picker_who_never_comes_back_loses_the_turn() {
+    let mut room = room(RoomType::Chain);
+    room.start_countdown_secs = 0;
+    let tokens = HashMap::from([
+      (ADMIN, join(&mut room, ADMIN, false).unwrap()),
+      (2, join(&mut room, 2, false).unwrap()),
+      (3, join(&mut room, 3, false).unwrap()),
+    ]);
+    room.start_game(setup("abcdef", "x"), tables());
+    let first = picker(&room);
+    disconnect(&mut room, first, tokens[&first]);
+    room.tick(&broadcast::channel(4).0);
+    assert_ne!(picker(&room), first);
+  }
+
+  #[test]
+  fn checks_change_nothing() {
+    let mut room = picking();
+    let timers = Timers {
+      pick_secs: Some(5),
+      ..Timers::default()
+    };
+    // Timers are refused mid-game, and checking max first leaves it alone
+    assert!(room.check_max_players(8).is_ok());
+    assert!(room.check_default_timers(&timers).is_err());
+    assert_eq!(room.max_players, 4);
+    assert_eq!(room.default_timers, Timers::default());
+    // Unchanged timers are always fine
+    assert!(room.check_default_timers(&Timers::default()).is_ok());
+    room.stop_game();
+    assert!(room.set_default_timers(timers).is_ok());
+    assert_eq!(room.default_timers, timers);
+  }
+}
@@ -0,0 +1,94 @@
+use super::pinyin_utils::PinyinTables;
+use crate::models::*;
+use std::collections::{HashMap, VecDeque};
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+
+/// Most problems one match may queue up
+pub const MAX_MATCH_ROUNDS: usize = 9;
+/// Accepted pause between rounds, in seconds
+pub const INTERMISSION_SECS: RangeInclusive<u64> = 3..=120;
+pub const DEFAULT_INTERMISSION: Duration = Duration::from_secs(10);
+
+/// Best-of-N chain games over a list of problems. Everyone with a fully
+/// correct answer wins the round; the match ends once someone holds a
+/// majority of the rounds or the problems run out.
+pub struct ChainMatch {
+  /// Problems still to play, in order
+  pub upcoming: VecDeque<GameSetup>,
+  pub rounds: usize,
+  /// Rounds started so far, counting the current one
+  pub started: usize,
+  /// Rounds already scored
+  pub scored: usize,
+  pub wins: HashMap<i64, usize>,
+  pub intermission: Duration,
+  /// Set while waiting to start the next round
+  pub next_round_at: Option<Instant>,
+  /// Standings stay up until another game starts
+  pub finished: bool,
+  /// Needed to launch rounds from the tick
+  pub table: PinyinTables,
+}
+
+impl ChainMatch {
+  pub fn new(problems: Vec<GameSetup>, intermission: Duration, table: PinyinTables) -> Self {
+    Self {
+      rounds: problems.len(),
+      upcoming: problems.into(),
+      started: 0,
+      scored: 0,
+      wins: HashMap::new(),
+      intermission,
+      next_round_at: None,
+      finished: false,
+      table,
+    }
+  }
+
+  pub fn wins_of(&self, pid: i64) -> usize {
+    self.wins.get(&pid).copied().unwrap_or(0)
+  }
+
+  /// Count a settled round and decide whether another one follows.
+  pub fn record_round(&mut self, winners: &[i64]) {
+    self.scored += 1;
+    for pid in winners {
+      *self.wins.entry(*pid).or_default() += 1;
+    }
+    let clinched = self.wins.values().any(|w| *w > self.rounds / 2);
+    if clinched || self.upcoming.is_empty() {
+      self.finished = true;
+      self.next_round_at = None;
+    } else {
+      self.next_round_at = Some(Instant::now() + self.intermission);
+    }
+  }
+
+  /// Everyone tied for the most wins; empty if nobody won a round
+  pub fn leaders(&self) -> Vec<i64> {
+    let best = self.wins.values().copied().max().unwrap_or(0);
+    if best == 0 {
+      return vec![];
+    }
+    let mut ids: Vec<i64> = self
+      .wins
+      .iter()
+      .filter(|(_, w)| **w == best)
+      .map(|(pid, _)| *pid)
+      .collect();
+    ids.sort();
+    ids
+  }
+
+  pub fn view(&self) -> MatchView {
+    MatchView {
+      round: self.started,
+      rounds: self.rounds,
+      next_round_ms: self
+        .next_round_at
+        .map(|t| t.saturating_duration_since(Instant::now()).as_millis() as u64),
+      finished: self.finished,
+    }
+  }
+}
@@ -0,0 +1,125 @@
+//! Rooms saved to disk on shutdown and loaded again on start.
+//!
+//! Instants are stored as signed milliseconds from the moment of saving and
+//! rebuilt against the moment of loading, so every timer stands still while the
+//! server is down.
+//!
+//! Not saved, and rebuilt empty on load: the room channels and cached views,
+//! chat rate limits, the lobby summary, idle tracking and ready flags. Everyone
+//! comes back offline with a fresh reconnect window. A best-of-N match is
+//! dropped, and its current round carries on as a single game.
+
+use super::pinyin_utils::PinyinTables;
+use super::room::Room;
+use crate::conf::Config;
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::{Duration, Instant};
+
+/// Replace `path` with `bytes`. They go to a file alongside first and are then
+/// renamed over it, so a crash mid-write can't leave a truncated file behind.
+pub fn write_atomic(path: &str, bytes: impl AsRef<[u8]>) -> std::io::Result<()> {
+  let tmp = format!("{}.tmp", path);
+  std::fs::write(&tmp, bytes)?;
+  std::fs::rename(&tmp, path)
+}
+
+pub fn save(path: &str, rooms: &[&Room]) -> Result<()> {
+  let json = serde_json::to_string(rooms)?;
+  write_atomic(path, json)?;
+  Ok(())
+}
+
+/// Read saved rooms, if there are any. The file is removed once read, so a
+/// crash later on doesn't bring back stale games.
+pub fn load(path: &str, config: &Config, pinyin_tables: &PinyinTables) -> Result<Vec<Room>> {
+  let json = match std::fs::read_to_string(path) {
+    Ok(j) => j,
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+    Err(e) => return Err(e.into()),
+  };
+  let mut rooms: Vec<Room> = serde_json::from_str(&json)?;
+  for room in &mut rooms {
+    room.restore(config, pinyin_tables.clone());
+  }
+  std::fs::remove_file(path)?;
+  Ok(rooms)
+}
+
+fn to_offset(t: Instant) -> i64 {
+  let now = Instant::now();
+  if t >= now {
+    (t - now).as_millis() as i64
+  } else {
+    -((now - t).as_millis() as i64)
+  }
+}
+
+fn from_offset(ms: i64) -> Instant {
+  let now = Instant::now();
+  let by = Duration::from_millis(ms.unsigned_abs());
+  if ms >= 0 {
+    now + by
+  } else {
+    // Older than this process's clock can go; the closest we can do is now
+    now.checked_sub(by).unwrap_or(now)
+  }
+}
+
+/// `#[serde(with)]` for an `Instant`, as milliseconds from now.
+pub mod instant {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(t: &Instant, s: S) -> Result<S::Ok, S::Error> {
+    to_offset(*t).serialize(s)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Instant, D::Error> {
+    i64::deserialize(d).map(from_offset)
+  }
+}
+
+/// `#[serde(with)]` for an `Option<Instant>`, as milliseconds from now.
+pub mod option_instant {
+  use super::*;
+
+  pub fn serialize<S: Serializer>(t: &Option<Instant>, s: S) -> Result<S::Ok, S::Error> {
+    t.map(to_offset).serialize(s)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Instant>, D::Error> {
+    Option::<i64>::deserialize(d).map(|ms| ms.map(from_offset))
+  }
+}
+
+/// `#[serde(with)]` for an `Option<Regex>`, as its pattern.
+pub mod option_regex {
+  use super::*;
+  use regex::Regex;
+
+  pub fn serialize<S: Serializer>(re: &Option<Regex>, s: S) -> Result<S::Ok, S::Error> {
+    re.as_ref().map(Regex::as_str).serialize(s)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Regex>, D::Error> {
+    Option::<String>::deserialize(d)?
+      .map(|p| Regex::new(&p).map_err(serde::de::Error::custom))
+      .transpose()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn write_atomic_replaces_and_tidies_up() {
+    let path = std::env::temp_dir().join(format!("quiz-time-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    write_atomic(path, "old").unwrap();
+    write_atomic(path, "new").unwrap();
+    assert_eq!(std::fs::read_to_string(path).unwrap(), "new");
+    assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+    std::fs::remove_file(path).unwrap();
+  }
+}
@@ -0,0 +1,96 @@
+//! Running totals per user across every game played on this server.
+
+use super::snapshot::write_atomic;
+use crate::models::LeaderboardRow;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// How one seated player fared in a settled game.
+pub struct PlayerOutcome {
+  pub id: i64,
+  pub name: String,
+  pub won: bool,
+  pub correct: bool,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct UserStats {
+  /// Name at the user's last game
+  pub name: String,
+  pub games: u64,
+  pub wins: u64,
+  pub correct: u64,
+}
+
+pub struct StatsStore {
+  /// Where totals are kept between runs; `None` keeps them in memory only
+  path: Option<String>,
+  users: Mutex<HashMap<i64, UserStats>>,
+}
+
+impl StatsStore {
+  /// Start from the saved totals, or from nothing if there aren't any yet.
+  pub fn load(path: Option<String>) -> Self {
+    let users = match path.as_deref().map(std::fs::read_to_string) {
+      Some(Ok(json)) => serde_json::from_str(&json).unwrap_or_else(|e| {
+        tracing::error!("could not parse stats, starting over: {}", e);
+        HashMap::new()
+      }),
+      Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => {
+        tracing::error!("could not read stats, starting over: {}", e);
+        HashMap::new()
+      }
+      _ => HashMap::new(),
+    };
+    Self {
+      path,
+      users: Mutex::new(users),
+    }
+  }
+
+  /// Add a settled game to the totals and save them. Blocks on file I/O.
+  pub fn record(&self, outcomes: &[PlayerOutcome]) {
+    // Held through the write so two games finishing together can't save out of order
+    let mut users = self.users.lock().unwrap();
+    for o in outcomes {
+      let s = users.entry(o.id).or_default();
+      s.name = o.name.clone();
+      s.games += 1;
+      s.wins += o.won as u64;
+      s.correct += o.correct as u64;
+    }
+    let Some(path) = &self.path else {
+      return;
+    };
+    let written = serde_json::to_string(&*users)
+      .map_err(std::io::Error::other)
+      .and_then(|json| write_atomic(path, json));
+    if let Err(e) = written {
+      tracing::warn!("failed to write {}: {}", path, e);
+    }
+  }
+
+  /// Most wins first, then most correct answers, then fewest games.
+  pub fn leaderboard(&self) -> Vec<LeaderboardRow> {
+    let users = self.users.lock().unwrap();
+    let mut rows: Vec<LeaderboardRow> = users
+      .iter()
+      .map(|(id, s)| LeaderboardRow {
+        id: *id,
+        name: s.name.clone(),
+        games: s.games,
+        wins: s.wins,
+        correct: s.correct,
+      })
+      .collect();
+    rows.sort_by(|a, b| {
+      b.wins
+        .cmp(&a.wins)
+        .then_with(|| b.correct.cmp(&a.correct))
+        .then_with(|| a.games.cmp(&b.games))
+        .then_with(|| a.name.cmp(&b.name))
+    });
+    rows
+  }
+}
@@ -8,7 +8,7 @@ pub mod routes;
 pub mod state;
 pub mod ws;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use state::AppState;
 use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -36,14 +36,79 @@ async fn main() -> Result<()> {
       // to update game state. This is safe.
       for r in bg_state.rooms.iter() {
         let mut room = r.value().write().await;
-        room.tick(&bg_state.global_tx);
+        if let Some(outcomes) = room.tick(&bg_state.global_tx) {
+          let state = bg_state.clone();
+          tokio::task::spawn_blocking(move || state.stats.record(&outcomes));
+        }
       }
     }
   });
 
-  let app = routes::app(app_state);
-  let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
-  tracing::info!("Listening on 0.0.0.0:8080");
-  axum::serve(listener, app).await?;
+  // Reap rooms nobody has been in for a while
+  if app_state.config.room_idle_secs > 0 {
+    let reap_state = app_state.clone();
+    let idle = std::time::Duration::from_secs(app_state.config.room_idle_secs);
+    tokio::spawn(async move {
+      let mut interval = tokio::time::interval(std::time::Duration::from_secs(30));
+      loop {
+        interval.tick().await;
+        let before = reap_state.rooms.len();
+        // A room someone is joining is write-locked, so `try_read` skips it this round
+        reap_state
+          .rooms
+          .retain(|_, r| !r.try_read().is_ok_and(|room| room.is_idle(idle)));
+        let reaped = before - reap_state.rooms.len();
+        if reaped > 0 {
+          tracing::info!("removed {} idle room(s)", reaped);
+          let _ = reap_state.global_tx.send(game::InternalMsg::LobbyChanged);
+        }
+      }
+    });
+  }
+
+  // Logouts and new accounts reach the users file in batches
+  let users_state = app_state.clone();
+  tokio::spawn(async move {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+    loop {
+      interval.tick().await;
+      users_state.flush_users();
+    }
+  });
+
+  let app = routes::app(app_state.clone());
+  let bind_addr = app_state.config.bind_addr;
+  let listener = tokio::net::TcpListener::bind(bind_addr)
+    .await
+    .with_context(|| format!("could not listen on {}", bind_addr))?;
+  tracing::info!("Listening on {}", bind_addr);
+  axum::serve(listener, app)
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
+  app_state.flush_users();
+  app_state.save_rooms().await?;
   Ok(())
 }
+
+/// Ctrl-C, or SIGTERM where there is one
+async fn shutdown_signal() {
+  let ctrl_c = async {
+    let _ = tokio::signal::ctrl_c().await;
+  };
+  #[cfg(unix)]
+  let terminate = async {
+    match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+      Ok(mut s) => {
+        s.recv().await;
+      }
+      Err(_) => std::future::pending().await,
+    }
+  };
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+  tokio::select! {
+    _ = ctrl_c => {},
+    _ = terminate => {},
+  }
+  tracing::info!("shutting down");
+}
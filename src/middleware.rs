@@ -18,19 +18,22 @@ pub async fn auth_middleware(
   let path = req.uri().path().to_string();
 
   // Whitelist
-  if path.starts_with("/login") || path.starts_with("/oauth-callback") || path == "/logout" {
+  if path.starts_with("/login")
+    || path.starts_with("/oauth-callback")
+    || path == "/logout"
+    || path == "/health"
+  {
     return next.run(req).await;
   }
 
   let mut user_val = None;
-  if let Some(token_str) = token {
-    if let Some(claims) = state.token_manager.parse_token(&token_str) {
-      if let Some(u) = state.users.get(&claims.sub) {
-        if claims.iat >= u.valid_after && u.role != Role::Banned {
-          user_val = Some(u.clone());
-        }
-      }
-    }
+  if let Some(token_str) = token
+    && let Some(claims) = state.token_manager.parse_token(&token_str)
+    && let Some(u) = state.users.get(&claims.sub)
+    && claims.iat >= u.valid_after
+    && u.role != Role::Banned
+  {
+    user_val = Some(u.clone());
   }
 
   if let Some(u) = user_val {
@@ -1,4 +1,7 @@
+use crate::auth::Role;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
 use strum::{Display, EnumString};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display, EnumString)]
@@ -31,9 +34,105 @@ pub enum GamePhase {
 pub enum PlayerStatus {
   #[default]
   Waiting,
-  Picking,   // Chain: active picker, Pinyin: active describer
+  Picking,   // Chain: active picker
   Answering, // Chain specific
+  Stopped,   // Chain: done picking, may answer early
   Submitted,
+  SittingOut, // Pinyin: not seated for this game
+  Describing, // Pinyin: current describer
+  Described,  // Pinyin: describer whose turn has passed
+  Guessing,   // Pinyin: guesser on their turn
+}
+
+/// Optional per-game settings, sent alongside the problem when starting a game.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameOptions {
+  /// Pinyin: number of describers before the guesser (defaults to everyone else)
+  pub describer_count: Option<usize>,
+  /// Pinyin: skips allowed per game before a skip forfeits it
+  pub skip_limit: Option<usize>,
+  /// Pinyin: whether the guesser sees every describer's text or only the last one
+  /// (defaults to the full chain)
+  pub guesser_sees_full_chain: Option<bool>,
+  /// Chain: reveal one more character to everyone every N seconds while answering
+  pub answer_hint_interval_secs: Option<u64>,
+  /// Chain: owners don't see their own characters until settlement
+  pub blind: bool,
+  /// Chain: minimum gap between a player's manual takes, in milliseconds
+  pub min_take_interval_ms: Option<u64>,
+  /// Chain: only send claimed cells while picking, hiding the problem length
+  pub hide_length: bool,
+  /// Chain: the last player left picking may decline the remaining characters
+  /// instead of being handed all of them; the pick timer running out still hands them over
+  pub last_pick_choice: bool,
+  /// Chain: several blanks, each checked on its own; empty means one answer
+  pub answer_parts: Vec<String>,
+  /// Chain: how submitted answers are compared against the expected ones;
+  /// unset follows the room's strict-match setting
+  pub match_strategy: Option<MatchStrategy>,
+  /// Chain: accept any single answer matching this pattern in full instead
+  pub answer_regex: Option<String>,
+  /// More hint text, unlocked one stage at a time after the plain hint
+  pub hint_stages: Vec<String>,
+  /// Unlock the next hint stage every N seconds; unset unlocks one per turn
+  pub hint_stage_secs: Option<u64>,
+  /// Overrides the room's default timers for this game
+  #[serde(flatten)]
+  pub timers: Timers,
+}
+
+/// Turn lengths in seconds. Unset entries fall back to the room default, then
+/// to the game's built-in default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timers {
+  /// Chain: time to take or stop on each picking turn
+  pub pick_secs: Option<u64>,
+  /// Chain: answer window once picking ends
+  pub answer_secs: Option<u64>,
+  /// Pinyin: time for each describer (and the guesser)
+  pub describer_secs: Option<u64>,
+}
+
+impl Timers {
+  pub fn or(self, fallback: Timers) -> Timers {
+    Timers {
+      pick_secs: self.pick_secs.or(fallback.pick_secs),
+      answer_secs: self.answer_secs.or(fallback.answer_secs),
+      describer_secs: self.describer_secs.or(fallback.describer_secs),
+    }
+  }
+}
+
+/// How a chain answer is judged against the expected text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MatchStrategy {
+  /// Character for character
+  Exact,
+  /// Ignoring case, extra whitespace and full-width/half-width differences
+  #[default]
+  Normalized,
+  /// Normalized, then within this many single-character edits
+  Fuzzy { max_distance: usize },
+}
+
+/// Problem and settings for one game, as supplied by the host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameSetup {
+  #[serde(default)]
+  pub problem: String,
+  /// The canonical answer, shown at settlement
+  #[serde(default)]
+  pub answer: String,
+  /// Other answers judged correct; the first stands in for `answer` if that's empty
+  #[serde(default)]
+  pub answers: Vec<String>,
+  #[serde(default)]
+  pub hint: String,
+  #[serde(flatten)]
+  pub options: GameOptions,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,16 +151,132 @@ pub enum InternalMsg {
   Kick {
     target: i64,
   },
+  /// Phase names as in `GamePhase`, plus `guessing` for the pinyin guesser's turn
+  Phase {
+    from: String,
+    to: String,
+  },
+  /// Sent on the global channel when the room list shown in the lobby changed
+  LobbyChanged,
+  Chat {
+    from: String,
+    text: String,
+    time: String,
+    /// From a spectator mid-game; players don't get these
+    spectators_only: bool,
+  },
 }
 
 #[derive(Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum ClientAction {
-  Action { action: String },
-  Answer { content: String },
+  Action {
+    action: String,
+  },
+  Answer {
+    content: String,
+  },
+  /// Unsubmitted answer text, kept server-side so it survives reconnects
+  Draft {
+    content: String,
+  },
+  /// Optional modes the client wants for this connection, from the server's `hello`
+  Hello {
+    #[serde(default)]
+    enable: Vec<String>,
+  },
+  /// Mark yourself ready (or not) for the next game
+  Ready {
+    ready: bool,
+  },
+  Chat {
+    text: String,
+  },
+}
+
+/// How one character of a queried text splits under the pinyin table.
+#[derive(Serialize)]
+pub struct CharDecomposition {
+  pub ch: char,
+  /// Every reading, most common first; `None` when the character isn't in the table
+  pub decomposition: Option<Vec<PinyinParts>>,
+}
+
+#[derive(Serialize)]
+pub struct PinyinParts {
+  pub pinyin: String,
+  pub initial: String,
+  #[serde(rename = "final")]
+  pub fin: String,
 }
 
+/// Dry-run result of a pinyin description, one entry per character.
 #[derive(Serialize)]
+pub struct DescribeCheck {
+  pub chars: Vec<CharCheck>,
+}
+
+#[derive(Serialize)]
+pub struct CharCheck {
+  pub ch: char,
+  pub error: Option<String>,
+}
+
+/// What happens to a seated player's place when they disconnect mid-game.
+///
+/// Reserving lets someone with a flaky connection rejoin without losing their
+/// cells or turn, at the cost of the game stalling (pinyin) or skipping them
+/// (chain) while they're away. Freeing immediately keeps the game moving and
+/// opens the seat for the next game, but a brief drop costs the player their place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DisconnectPolicy {
+  /// Keep the seat for this many seconds before forfeiting
+  Reserve { secs: u64 },
+  /// Remove the player as soon as they disconnect
+  FreeImmediately,
+}
+
+impl Default for DisconnectPolicy {
+  fn default() -> Self {
+    DisconnectPolicy::Reserve {
+      secs: crate::game::room::DISCONNECT_GRACE.as_secs(),
+    }
+  }
+}
+
+impl DisconnectPolicy {
+  /// How long a disconnected player keeps their place
+  pub fn grace(&self) -> Duration {
+    match self {
+      DisconnectPolicy::Reserve { secs } => Duration::from_secs(*secs),
+      DisconnectPolicy::FreeImmediately => Duration::ZERO,
+    }
+  }
+}
+
+/// How much of a running game non-admin spectators get to see.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpectatorVision {
+  /// Colors, counts and statuses, like a neutral observer
+  #[default]
+  Neutral,
+  /// Every character and answer, the same as an admin spectating
+  Full,
+}
+
+/// What an admin sees of the prepared game: sizes and settings, never the text.
+#[derive(Serialize)]
+pub struct PendingConfig {
+  pub problem_len: usize,
+  pub answer_len: usize,
+  pub has_hint: bool,
+  pub options: GameOptions,
+}
+
+/// One room as listed in the lobby.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct RoomSummary {
   pub id: String,
   pub name: String,
@@ -69,6 +284,55 @@ pub struct RoomSummary {
   pub phase: GamePhase,
   pub player_count: usize,
   pub max_players: usize,
+  /// Needs a password to join
+  pub locked: bool,
+}
+
+/// One user's totals on `/leaderboard`.
+#[derive(Serialize)]
+pub struct LeaderboardRow {
+  pub id: i64,
+  pub name: String,
+  pub games: u64,
+  pub wins: u64,
+  pub correct: u64,
+}
+
+#[derive(Serialize)]
+pub struct AdminStats {
+  pub total_rooms: usize,
+  pub rooms_by_phase: BTreeMap<String, usize>,
+  pub players_online: usize,
+  pub spectators: usize,
+  pub rooms: Vec<RoomStats>,
+}
+
+#[derive(Serialize)]
+pub struct RoomStats {
+  pub id: String,
+  pub name: String,
+  pub room_type: RoomType,
+  pub phase: GamePhase,
+  pub players: usize,
+  pub players_online: usize,
+  pub spectators: usize,
+  pub max_players: usize,
+}
+
+#[derive(Serialize)]
+pub struct UserList {
+  pub total: usize,
+  pub page: usize,
+  pub per_page: usize,
+  pub users: Vec<UserEntry>,
+}
+
+#[derive(Serialize)]
+pub struct UserEntry {
+  pub id: i64,
+  pub name: String,
+  pub role: Role,
+  pub valid_after: i64,
 }
 
 #[derive(Serialize)]
@@ -79,12 +343,54 @@ pub struct ClientView {
   pub phase: GamePhase,
   pub hint: String,
   pub deadline_ms: Option<u64>,
+  /// Server wall clock (epoch millis) when this view was built, for timer sync
+  pub server_now_ms: u64,
   pub is_admin: bool,
   // Only sent if is_admin is true
   #[serde(skip_serializing_if = "Option::is_none")]
   pub admin_ids: Option<Vec<i64>>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub banned: Option<Vec<BannedUser>>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub default_timers: Option<Timers>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub pending_game: Option<PendingConfig>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub disconnect_policy: Option<DisconnectPolicy>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub turn_toasts: Option<bool>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub start_countdown_secs: Option<u64>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub spectator_vision: Option<SpectatorVision>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub strict_match: Option<bool>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tone_sensitive: Option<bool>,
+  // Only sent if is_admin is true
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub require_ready: Option<bool>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub chain_match: Option<MatchView>,
+  /// Chain: final scores, only at settlement
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub leaderboard: Option<Vec<ScoreEntry>>,
+  /// An admin froze the game; timers stand still and nobody can act
+  pub paused: bool,
   pub players: Vec<PlayerView>,
   pub max_players: usize,
+  pub max_spectators: Option<usize>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub cell_size_px: Option<u16>,
 
   // Optional Game-Specific Data
   #[serde(skip_serializing_if = "Option::is_none")]
@@ -97,6 +403,75 @@ pub struct ClientView {
   pub winner: Option<bool>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub correct_answer: Option<String>,
+  // Only ever the viewer's own draft
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub my_draft: Option<String>,
+  /// Whether the viewer may send an action (take/stop, skip) right now
+  pub can_act: bool,
+  /// Whether the viewer may submit an answer right now
+  pub can_answer: bool,
+  /// Chain: how many players have yet to submit, while the game is running
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub pending_submissions: Option<usize>,
+  /// Chain: when each player submitted, in ms into the answer window (negative if early)
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub submit_ms: Option<BTreeMap<i64, i64>>,
+  /// Chain: each player's cells in the order taken, keyed by player id
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub takes: Option<BTreeMap<i64, Vec<TakeRecord>>>,
+  /// Chain: number of blanks, only sent for multi-part answers
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub answer_parts: Option<usize>,
+  /// Chain: per-player correctness of each blank (a single entry for a
+  /// one-part answer), once answers are visible
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub part_results: Option<BTreeMap<i64, Vec<bool>>>,
+  /// Chain: how `part_results` were judged, sent alongside them
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub match_strategy: Option<MatchStrategy>,
+  /// Chain: the current picker is the last one left and takes the rest or declines
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub final_pick: bool,
+  /// Chain: the viewer may take back their latest take this turn
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub can_undo: bool,
+  /// Chain: cells nobody has taken yet, while picking; withheld when the length is hidden
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub remaining_chars: Option<usize>,
+  /// Chain: counting down to the first turn; `deadline_ms` is when it starts
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub starting: bool,
+  /// Settlement: how long the game ran, pauses aside
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub game_duration_ms: Option<u64>,
+  /// Pinyin settlement: each player's pace over their turns
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub turn_times: Option<Vec<TurnTiming>>,
+}
+
+/// Game-specific part of a `ClientView`, built by each game's `get_view_data`.
+pub struct GameViewData {
+  pub phase: GamePhase,
+  pub hint: String,
+  pub deadline: Option<Instant>,
+  pub grid: Option<Vec<GridCell>>,
+  pub pinyin_state: Option<PinyinSpecificView>,
+  pub winner: Option<bool>,
+  pub correct_answer: Option<String>,
+  pub my_draft: Option<String>,
+  pub can_act: bool,
+  pub can_answer: bool,
+  pub pending_submissions: Option<usize>,
+  pub submit_ms: Option<BTreeMap<i64, i64>>,
+  pub takes: Option<BTreeMap<i64, Vec<TakeRecord>>>,
+  pub answer_parts: Option<usize>,
+  pub part_results: Option<BTreeMap<i64, Vec<bool>>>,
+  pub match_strategy: Option<MatchStrategy>,
+  pub final_pick: bool,
+  pub can_undo: bool,
+  pub remaining_chars: Option<usize>,
+  pub starting: bool,
+  pub game_duration_ms: Option<u64>,
 }
 
 #[derive(Serialize)]
@@ -107,10 +482,17 @@ pub struct PlayerView {
   pub status: PlayerStatus,
   pub is_me: bool,
   pub is_online: bool,
+  /// Time left before a disconnected player forfeits; only set during a game
+  pub disconnect_remaining_ms: Option<u64>,
   pub is_active_turn: bool,
   pub score_display: Option<String>,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub answer: Option<String>,
+  /// Chain: whether the player has handed in an answer, visible to everyone
+  /// even while the answer itself is hidden
+  pub submitted: bool,
+  /// Ready for the next game; always false while one is running
+  pub ready: bool,
   pub is_spectator: bool,
   pub is_admin: bool,
 }
@@ -118,8 +500,21 @@ pub struct PlayerView {
 // Chain Specific
 #[derive(Serialize)]
 pub struct GridCell {
+  /// Row break in a multi-line problem, rendered as a line break rather than a cell
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub is_break: bool,
   pub owner_color_hue: Option<u16>,
   pub char_content: Option<char>, // Strictly None if not allowed to see
+  /// 1-based position in the order cells were taken; only at settlement
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub take_seq: Option<usize>,
+}
+
+/// One cell a chain player took, and on which picking turn (1-based).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TakeRecord {
+  pub index: usize,
+  pub turn: usize,
 }
 
 // Pinyin Specific
@@ -132,13 +527,111 @@ pub struct PinyinSpecificView {
   pub history: Vec<PinyinHistoryItem>,
   pub my_prompt: Option<String>,
   pub is_first_turn: bool,
+  pub tone_sensitive: bool,
   pub is_guessing_turn: bool,
+  /// Sent to the current describer when every character is banned for them
+  pub no_legal_moves: bool,
+  pub skips_remaining: usize,
   pub end_message: Option<String>,
 }
 
-#[derive(Clone, Serialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct PinyinHistoryItem {
   pub player: i64,
   pub content: String,
   pub is_guess: bool,
+  /// Milliseconds since the game started
+  pub elapsed_ms: u64,
+}
+
+/// Someone kept out of a room, for the admins' unban list.
+#[derive(Serialize)]
+pub struct BannedUser {
+  pub id: i64,
+  /// As of the ban
+  pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct ScoreEntry {
+  pub id: i64,
+  pub name: String,
+  pub points: u64,
+  /// 1-based; tied players share one
+  pub rank: usize,
+  pub submitted: bool,
+  /// Time from the answer window opening to the submission; negative if early
+  pub answer_ms: Option<i64>,
+}
+
+/// One player's turns in a finished pinyin game.
+#[derive(Serialize)]
+pub struct TurnTiming {
+  pub id: i64,
+  pub name: String,
+  pub turns: usize,
+  pub avg_ms: u64,
+}
+
+/// Progress of a best-of-N chain match.
+#[derive(Serialize)]
+pub struct MatchView {
+  /// Rounds started so far, counting the current one
+  pub round: usize,
+  pub rounds: usize,
+  /// Time until the next round starts, during an intermission
+  pub next_round_ms: Option<u64>,
+  pub finished: bool,
+}
+
+// Results
+/// Everything a finished game leaves behind, written out at settlement.
+#[derive(Serialize)]
+pub struct GameRecord {
+  pub room_id: String,
+  pub room_name: String,
+  pub finished_at: chrono::DateTime<chrono::Utc>,
+  #[serde(flatten)]
+  pub game: GameRecordData,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "room_type", rename_all = "snake_case")]
+pub enum GameRecordData {
+  Chain {
+    problem: String,
+    answer: String,
+    alt_answers: Vec<String>,
+    hint: String,
+    /// The pattern answers were matched against, if any
+    answer_regex: Option<String>,
+    players: Vec<ChainPlayerRecord>,
+  },
+  Pinyin {
+    answer: String,
+    alt_answers: Vec<String>,
+    hint: String,
+    /// In seating order; the last one guessed
+    players: Vec<PlayerRecord>,
+    winner: bool,
+    end_reason: Option<String>,
+    history: Vec<PinyinHistoryItem>,
+  },
+}
+
+#[derive(Serialize)]
+pub struct PlayerRecord {
+  pub id: i64,
+  pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct ChainPlayerRecord {
+  pub id: i64,
+  pub name: String,
+  pub answer: Option<String>,
+  /// One entry per blank, or a single one for a plain answer
+  pub correct: Vec<bool>,
+  pub cells: usize,
+  pub points: u64,
 }
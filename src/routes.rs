@@ -1,4 +1,11 @@
-use crate::models::{GamePhase, RoomType};
+use crate::game::room::{
+  CELL_SIZE_PX, JoinPassword, RESERVE_SECS, START_COUNTDOWN_SECS, validate_timers,
+};
+use crate::game::series::{DEFAULT_INTERMISSION, INTERMISSION_SECS};
+use crate::models::{
+  AdminStats, DisconnectPolicy, GamePhase, GameSetup, InternalMsg, RoomStats, RoomSummary,
+  RoomType, SpectatorVision, Timers, UserEntry, UserList,
+};
 use crate::{
   auth::{Role, User},
   error::AppError,
@@ -9,13 +16,14 @@ use crate::{
 use askama::Template;
 use axum::{
   Json, Router,
-  extract::{Form, Path, State},
-  http::StatusCode,
+  extract::{Form, Path, Query, State},
+  http::{StatusCode, header},
   middleware,
   response::{Html, IntoResponse, Redirect, Response},
   routing::{get, post},
 };
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tower::ServiceBuilder;
 use tower_cookies::CookieManagerLayer;
 use tower_http::compression::CompressionLayer;
@@ -27,13 +35,23 @@ use uuid::Uuid;
 struct LoginTemplate {
   error: Option<String>,
   user: Option<User>,
+  github_enabled: bool,
 }
 
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
   user: Option<User>,
-  rooms: Vec<RoomSummaryView>,
+  rooms: Vec<RoomSummary>,
+}
+
+#[derive(Template)]
+#[template(path = "room_password.html")]
+struct RoomPasswordTemplate {
+  user: Option<User>,
+  room_id: String,
+  is_spectate: bool,
+  error: Option<String>,
 }
 
 #[derive(Template)]
@@ -45,15 +63,6 @@ struct RoomTemplate {
   is_admin: bool,
 }
 
-struct RoomSummaryView {
-  id: String,
-  name: String,
-  mode: String,
-  phase: String,
-  count: usize,
-  max: usize,
-}
-
 pub fn app(state: Arc<AppState>) -> Router {
   let auth_routes = Router::new()
     .route("/", get(index))
@@ -63,10 +72,37 @@ pub fn app(state: Arc<AppState>) -> Router {
       get(enter_room).put(update_room).delete(delete_room),
     )
     .route("/room/{id}/kick", post(kick_player))
+    .route("/room/{id}/ban", post(ban_player))
+    .route("/room/{id}/unban", post(unban_player))
+    .route("/room/{id}/transfer", post(transfer_room))
     .route("/room/{id}/spectate", get(spectate_room))
+    .route("/room/{id}/unlock", post(unlock_room))
     .route("/room/{id}/start", post(start_game))
     .route("/room/{id}/stop", post(stop_game))
+    .route("/room/{id}/rematch", post(rematch))
+    .route("/room/{id}/match", post(start_match))
+    .route("/room/{id}/reshuffle", post(reshuffle))
+    .route(
+      "/room/{id}/prepare",
+      post(prepare_game).delete(clear_prepared),
+    )
+    .route("/room/{id}/prepare/start", post(start_prepared))
+    .route("/room/{id}/reveal", post(reveal_all))
+    .route("/room/{id}/finish", post(finish_game))
+    .route("/room/{id}/pause", post(pause_game))
+    .route("/room/{id}/resume", post(resume_game))
+    .route("/room/{id}/transcript", get(download_transcript))
+    .route("/room/{id}/state", get(room_state))
+    .route("/room/{id}/validate_describe", post(validate_describe))
+    .route("/pinyin/decompose", get(decompose_text))
+    .route("/leaderboard", get(leaderboard))
+    .route("/admin/stats", get(admin_stats))
+    .route("/metrics", get(metrics))
+    .route("/admin/users", get(admin_users))
+    .route("/admin/users/{id}/role", post(set_user_role))
+    .route("/admin/reload-dict", post(reload_dict))
     .route("/ws", get(ws::ws_handler))
+    .route("/ws/lobby", get(ws::lobby_handler))
     .layer(middleware::from_fn_with_state(
       state.clone(),
       auth_middleware,
@@ -74,12 +110,13 @@ pub fn app(state: Arc<AppState>) -> Router {
 
   let public_routes = Router::new()
     .route("/login", get(login_page).post(login_submit))
-    .route("/login/codeberg", get(crate::auth::oauth::login_codeberg))
+    .route("/login/{provider}", get(crate::auth::oauth::login))
     .route(
-      "/oauth-callback/codeberg",
-      get(crate::auth::oauth::callback_codeberg),
+      "/oauth-callback/{provider}",
+      get(crate::auth::oauth::callback),
     )
-    .route("/logout", get(logout));
+    .route("/logout", get(logout))
+    .route("/health", get(health));
 
   Router::new()
     .merge(public_routes)
@@ -109,34 +146,18 @@ async fn index(
   State(state): State<Arc<AppState>>,
   axum::Extension(user): axum::Extension<User>,
 ) -> impl IntoResponse {
-  let mut rooms = vec![];
-  for r_lock in state.rooms.iter() {
-    let r = r_lock.read().await;
-    let phase = match &r.session {
-      crate::game::room::GameSession::None => GamePhase::Waiting,
-      crate::game::room::GameSession::Chain(g) => g.phase,
-      crate::game::room::GameSession::Pinyin(g) => g.phase,
-    };
-
-    rooms.push(RoomSummaryView {
-      id: r.id.to_string(),
-      name: r.name.clone(),
-      mode: r.room_type.to_string(),
-      phase: phase.to_string(),
-      count: r.players.iter().filter(|p| !p.1.is_spectator).count(),
-      max: r.max_players,
-    });
-  }
+  let rooms = state.room_summaries().await;
   render(IndexTemplate {
     user: Some(user),
     rooms,
   })
 }
 
-async fn login_page() -> impl IntoResponse {
+async fn login_page(State(state): State<Arc<AppState>>) -> impl IntoResponse {
   render(LoginTemplate {
     error: None,
     user: None,
+    github_enabled: state.github_client.is_some(),
   })
 }
 
@@ -162,6 +183,7 @@ async fn login_submit(
       return render(LoginTemplate {
         error: Some("Banned".into()),
         user: None,
+        github_enabled: state.github_client.is_some(),
       })
       .into_response();
     }
@@ -177,6 +199,7 @@ async fn login_submit(
     render(LoginTemplate {
       error: Some("Invalid credentials".into()),
       user: None,
+      github_enabled: state.github_client.is_some(),
     })
     .into_response()
   }
@@ -186,12 +209,13 @@ async fn logout(
   State(state): State<Arc<AppState>>,
   cookies: tower_cookies::Cookies,
 ) -> impl IntoResponse {
-  if let Some(token) = cookies.get("token") {
-    if let Some(claims) = state.token_manager.parse_token(token.value()) {
-      if let Some(mut user) = state.users.get_mut(&claims.sub) {
-        user.valid_after = chrono::Utc::now().timestamp();
-      }
-    }
+  if let Some(token) = cookies.get("token")
+    && let Some(claims) = state.token_manager.parse_token(token.value())
+    && let Some(mut user) = state.users.get_mut(&claims.sub)
+  {
+    user.valid_after = chrono::Utc::now().timestamp();
+    drop(user);
+    state.mark_users_changed();
   }
   cookies.remove(tower_cookies::Cookie::new("token", ""));
   Redirect::to("/login").into_response()
@@ -202,6 +226,30 @@ struct CreateRoomForm {
   name: String,
   rtype: RoomType,
   max: usize,
+  /// Blank leaves spectating unlimited
+  #[serde(default, deserialize_with = "empty_as_none")]
+  max_spectators: Option<u64>,
+  #[serde(default, deserialize_with = "empty_as_none")]
+  default_pick_secs: Option<u64>,
+  #[serde(default, deserialize_with = "empty_as_none")]
+  default_answer_secs: Option<u64>,
+  #[serde(default, deserialize_with = "empty_as_none")]
+  default_describer_secs: Option<u64>,
+  /// Blank leaves the room open to everyone
+  #[serde(default)]
+  join_password: String,
+  /// Checkbox; present as `on` when ticked
+  #[serde(default)]
+  open_spectating: Option<String>,
+}
+
+// Blank number inputs still submit as `field=`
+fn empty_as_none<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+  let s: Option<String> = serde::Deserialize::deserialize(d)?;
+  match s.as_deref().map(str::trim) {
+    None | Some("") => Ok(None),
+    Some(v) => v.parse().map(Some).map_err(serde::de::Error::custom),
+  }
 }
 
 async fn create_room(
@@ -212,8 +260,29 @@ async fn create_room(
   if user.role != Role::Admin {
     return Redirect::to("/").into_response();
   }
+  let timers = Timers {
+    pick_secs: form.default_pick_secs,
+    answer_secs: form.default_answer_secs,
+    describer_secs: form.default_describer_secs,
+  };
+  if let Err(e) = validate_timers(&timers) {
+    return (StatusCode::BAD_REQUEST, e).into_response();
+  }
   let id = Uuid::now_v7();
-  let room = crate::game::room::Room::new(id, form.name, form.rtype, form.max, user.id.clone());
+  let mut room = crate::game::room::Room::new(
+    id,
+    form.name,
+    form.rtype,
+    form.max,
+    user.id,
+    timers,
+    &state.config,
+  );
+  if !form.join_password.is_empty() {
+    room.join_password = Some(JoinPassword::new(&form.join_password));
+  }
+  room.open_spectating = form.open_spectating.is_some();
+  room.max_spectators = form.max_spectators.map(|n| n as usize);
   state
     .rooms
     .insert(id, Arc::new(tokio::sync::RwLock::new(room)));
@@ -230,6 +299,9 @@ async fn enter_room(
     None => return Redirect::to("/").into_response(),
   };
   let room = r_lock.read().await;
+  if !room.can_enter(user.id, user.is_admin(), false) {
+    return password_prompt(user, id, false, None);
+  }
   let is_admin = room.admin_ids.contains(&user.id) || user.role == Role::Admin;
   render(RoomTemplate {
     user: Some(user),
@@ -241,16 +313,64 @@ async fn enter_room(
 }
 
 async fn spectate_room(
-  State(_state): State<Arc<AppState>>,
+  State(state): State<Arc<AppState>>,
   Path(id): Path<Uuid>,
   axum::Extension(user): axum::Extension<User>,
-) -> impl IntoResponse {
+) -> Response {
+  if let Some(r_lock) = state.rooms.get(&id)
+    && !r_lock
+      .read()
+      .await
+      .can_enter(user.id, user.is_admin(), true)
+  {
+    return password_prompt(user, id, true, None);
+  }
   render(RoomTemplate {
     user: Some(user),
     room_id: id.to_string(),
     is_spectate: true,
     is_admin: false,
   })
+  .into_response()
+}
+
+fn password_prompt(user: User, id: Uuid, is_spectate: bool, error: Option<String>) -> Response {
+  (
+    StatusCode::FORBIDDEN,
+    render(RoomPasswordTemplate {
+      user: Some(user),
+      room_id: id.to_string(),
+      is_spectate,
+      error,
+    }),
+  )
+    .into_response()
+}
+
+#[derive(serde::Deserialize)]
+struct UnlockRoomForm {
+  password: String,
+  #[serde(default)]
+  spectate: bool,
+}
+
+async fn unlock_room(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+  Form(form): Form<UnlockRoomForm>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return Redirect::to("/").into_response();
+  };
+  if !r_lock.write().await.admit(user.id, &form.password) {
+    return password_prompt(user, id, form.spectate, Some("Wrong password".into()));
+  }
+  if form.spectate {
+    Redirect::to(&format!("/room/{}/spectate", id)).into_response()
+  } else {
+    Redirect::to(&format!("/room/{}", id)).into_response()
+  }
 }
 
 #[derive(serde::Deserialize)]
@@ -258,6 +378,35 @@ struct UpdateRoomJson {
   name: String,
   max: usize,
   admins: Vec<i64>,
+  #[serde(default)]
+  cell_size_px: Option<u16>,
+  /// `None` lifts the cap
+  #[serde(default)]
+  max_spectators: Option<usize>,
+  /// Left unchanged when omitted
+  #[serde(default)]
+  default_timers: Option<Timers>,
+  /// Left unchanged when omitted
+  #[serde(default)]
+  disconnect_policy: Option<DisconnectPolicy>,
+  /// Left unchanged when omitted
+  #[serde(default)]
+  turn_toasts: Option<bool>,
+  /// Left unchanged when omitted
+  #[serde(default)]
+  start_countdown_secs: Option<u64>,
+  /// Left unchanged when omitted
+  #[serde(default)]
+  spectator_vision: Option<SpectatorVision>,
+  /// Left unchanged when omitted
+  #[serde(default)]
+  strict_match: Option<bool>,
+  /// Left unchanged when omitted
+  #[serde(default)]
+  tone_sensitive: Option<bool>,
+  /// Left unchanged when omitted
+  #[serde(default)]
+  require_ready: Option<bool>,
 }
 
 async fn update_room(
@@ -265,20 +414,96 @@ async fn update_room(
   Path(id): Path<Uuid>,
   axum::Extension(user): axum::Extension<User>,
   Json(payload): Json<UpdateRoomJson>,
-) -> impl IntoResponse {
+) -> Response {
   if let Some(r_lock) = state.rooms.get(&id) {
     let mut room = r_lock.write().await;
     if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
-      return StatusCode::FORBIDDEN;
+      return StatusCode::FORBIDDEN.into_response();
+    }
+    if let Some(px) = payload.cell_size_px
+      && !CELL_SIZE_PX.contains(&px)
+    {
+      return (
+        StatusCode::BAD_REQUEST,
+        format!(
+          "Cell size must be between {} and {} px",
+          CELL_SIZE_PX.start(),
+          CELL_SIZE_PX.end()
+        ),
+      )
+        .into_response();
+    }
+    if let Some(DisconnectPolicy::Reserve { secs }) = payload.disconnect_policy
+      && !RESERVE_SECS.contains(&secs)
+    {
+      return (
+        StatusCode::BAD_REQUEST,
+        format!(
+          "Seat reservation must be between {} and {} seconds",
+          RESERVE_SECS.start(),
+          RESERVE_SECS.end()
+        ),
+      )
+        .into_response();
+    }
+    if let Some(secs) = payload.start_countdown_secs
+      && !START_COUNTDOWN_SECS.contains(&secs)
+    {
+      return (
+        StatusCode::BAD_REQUEST,
+        format!(
+          "Start countdown must be between {} and {} seconds",
+          START_COUNTDOWN_SECS.start(),
+          START_COUNTDOWN_SECS.end()
+        ),
+      )
+        .into_response();
+    }
+    // Check everything before changing anything, so a refused update leaves the room as it was
+    let checked =
+      room
+        .check_max_players(payload.max)
+        .and_then(|()| match &payload.default_timers {
+          Some(timers) => room.check_default_timers(timers),
+          None => Ok(()),
+        });
+    if let Err(e) = checked {
+      return (StatusCode::CONFLICT, e).into_response();
     }
-    room.name = payload.name;
     room.max_players = payload.max;
+    if let Some(timers) = payload.default_timers {
+      room.default_timers = timers;
+    }
+    room.name = payload.name;
+    room.cell_size_px = payload.cell_size_px;
+    room.max_spectators = payload.max_spectators;
+    if let Some(policy) = payload.disconnect_policy {
+      room.disconnect_policy = policy;
+    }
+    if let Some(enabled) = payload.turn_toasts {
+      room.set_turn_toasts(enabled);
+    }
+    if let Some(secs) = payload.start_countdown_secs {
+      room.start_countdown_secs = secs;
+    }
+    if let Some(vision) = payload.spectator_vision {
+      room.spectator_vision = vision;
+    }
+    if let Some(strict) = payload.strict_match {
+      room.strict_match = strict;
+    }
+    if let Some(toned) = payload.tone_sensitive {
+      room.tone_sensitive = toned;
+    }
+    if let Some(required) = payload.require_ready {
+      room.require_ready = required;
+    }
     room.admin_ids = payload.admins.into_iter().collect();
     if user.role != Role::Admin {
-      room.admin_ids.insert(user.id.clone());
+      room.admin_ids.insert(user.id);
     }
   }
-  StatusCode::OK
+  StatusCode::OK.into_response()
 }
 
 async fn delete_room(
@@ -299,6 +524,7 @@ async fn delete_room(
   }
 
   state.rooms.remove(&id);
+  let _ = state.global_tx.send(InternalMsg::LobbyChanged);
   StatusCode::OK
 }
 
@@ -324,34 +550,324 @@ async fn kick_player(
   StatusCode::OK
 }
 
+async fn ban_player(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+  Json(payload): Json<KickPlayerJson>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let name = match room.players.get(&payload.user_id) {
+    Some(p) => p.name.clone(),
+    None => match state.users.get(&payload.user_id) {
+      Some(u) => u.name.clone(),
+      None => return (StatusCode::NOT_FOUND, "No such user").into_response(),
+    },
+  };
+  match room.ban(user.id, payload.user_id, name) {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn unban_player(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+  Json(payload): Json<KickPlayerJson>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.unban(payload.user_id) {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
 #[derive(serde::Deserialize)]
-struct StartGameJson {
-  problem: String,
-  answer: String,
-  hint: String,
+struct TransferRoomJson {
+  new_owner: i64,
+  /// Also remove the caller from the room's admins
+  #[serde(default)]
+  step_down: bool,
+}
+
+async fn transfer_room(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+  Json(payload): Json<TransferRoomJson>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.transfer(user.id, payload.new_owner, payload.step_down) {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+/// Trim host input the same way for every way of starting a game.
+fn clean_setup(payload: GameSetup) -> GameSetup {
+  let mut answers: Vec<String> = payload
+    .answers
+    .iter()
+    .map(|a| a.trim().to_string())
+    .filter(|a| !a.is_empty())
+    .collect();
+  let mut answer = payload.answer.trim().to_string();
+  if answer.is_empty() && !answers.is_empty() {
+    answer = answers.remove(0);
+  }
+  answers.retain(|a| *a != answer);
+  let mut options = payload.options;
+  options.hint_stages = options
+    .hint_stages
+    .iter()
+    .map(|s| s.trim().to_string())
+    .filter(|s| !s.is_empty())
+    .collect();
+  GameSetup {
+    problem: payload.problem.trim_end().to_string(),
+    answer,
+    answers,
+    hint: payload.hint.trim().to_string(),
+    options,
+  }
 }
 
 async fn start_game(
   State(state): State<Arc<AppState>>,
   Path(id): Path<Uuid>,
   axum::Extension(user): axum::Extension<User>,
-  Json(payload): Json<StartGameJson>,
+  Json(payload): Json<GameSetup>,
 ) -> impl IntoResponse {
   if let Some(r_lock) = state.rooms.get(&id) {
     let mut room = r_lock.write().await;
     if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
       return StatusCode::FORBIDDEN.into_response();
     }
-    room.start_game(
-      payload.problem.trim_end().to_string(),
-      payload.answer.trim().to_string(),
-      payload.hint.trim().to_string(),
-      state.pinyin_table.clone(),
-    );
+    room.start_game(clean_setup(payload), state.pinyin_tables());
   }
   StatusCode::OK.into_response()
 }
 
+#[derive(serde::Deserialize)]
+struct StartMatchJson {
+  problems: Vec<GameSetup>,
+  /// Pause between rounds; defaults to ten seconds
+  #[serde(default)]
+  intermission_secs: Option<u64>,
+}
+
+async fn start_match(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+  Json(payload): Json<StartMatchJson>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let intermission = match payload.intermission_secs {
+    Some(secs) if !INTERMISSION_SECS.contains(&secs) => {
+      return (
+        StatusCode::BAD_REQUEST,
+        format!(
+          "Intermission must be between {} and {} seconds",
+          INTERMISSION_SECS.start(),
+          INTERMISSION_SECS.end()
+        ),
+      )
+        .into_response();
+    }
+    Some(secs) => Duration::from_secs(secs),
+    None => DEFAULT_INTERMISSION,
+  };
+  let problems = payload.problems.into_iter().map(clean_setup).collect();
+  match room.start_match(problems, intermission, state.pinyin_tables()) {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn prepare_game(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+  Json(payload): Json<GameSetup>,
+) -> impl IntoResponse {
+  if let Some(r_lock) = state.rooms.get(&id) {
+    let mut room = r_lock.write().await;
+    if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+      return StatusCode::FORBIDDEN;
+    }
+    room.set_pending_game(Some(clean_setup(payload)));
+  }
+  StatusCode::OK
+}
+
+async fn clear_prepared(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> impl IntoResponse {
+  if let Some(r_lock) = state.rooms.get(&id) {
+    let mut room = r_lock.write().await;
+    if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+      return StatusCode::FORBIDDEN;
+    }
+    room.set_pending_game(None);
+  }
+  StatusCode::OK
+}
+
+async fn start_prepared(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.start_prepared(state.pinyin_tables()) {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn reshuffle(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.reshuffle() {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn rematch(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+  Json(payload): Json<GameSetup>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.rematch(clean_setup(payload), state.pinyin_tables()) {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn reveal_all(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.reveal_all() {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn finish_game(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.force_finish() {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn pause_game(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.pause() {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn resume_game(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let mut room = r_lock.write().await;
+  if !room.admin_ids.contains(&user.id) && user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match room.resume() {
+    Ok(()) => StatusCode::OK.into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
 async fn stop_game(
   State(state): State<Arc<AppState>>,
   Path(id): Path<Uuid>,
@@ -366,3 +882,361 @@ async fn stop_game(
   }
   StatusCode::OK.into_response()
 }
+
+#[derive(serde::Deserialize)]
+struct ValidateDescribeJson {
+  content: String,
+}
+
+async fn validate_describe(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+  Json(payload): Json<ValidateDescribeJson>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let room = r_lock.read().await;
+  let spectate = room.players.get(&user.id).is_none_or(|p| p.is_spectator);
+  if !room.can_enter(user.id, user.is_admin(), spectate) {
+    return (StatusCode::FORBIDDEN, "This room needs a password").into_response();
+  }
+  match room.validate_describe(user.id, &payload.content) {
+    Ok(check) => Json(check).into_response(),
+    Err(e) => (StatusCode::CONFLICT, e).into_response(),
+  }
+}
+
+async fn download_transcript(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let room = r_lock.read().await;
+  let spectate = room.players.get(&user.id).is_none_or(|p| p.is_spectator);
+  if !room.can_enter(user.id, user.is_admin(), spectate) {
+    return (StatusCode::FORBIDDEN, "This room needs a password").into_response();
+  }
+  let text = match &room.session {
+    crate::game::room::GameSession::Pinyin(g) if g.phase == GamePhase::Settlement => {
+      g.transcript(&room.name, &room.players)
+    }
+    crate::game::room::GameSession::Chain(g) if g.phase == GamePhase::Settlement => {
+      g.transcript(&room.name, &room.players)
+    }
+    _ => return (StatusCode::CONFLICT, "Game has not finished").into_response(),
+  };
+  (
+    [
+      (
+        header::CONTENT_TYPE,
+        "text/markdown; charset=utf-8".to_string(),
+      ),
+      (
+        header::CONTENT_DISPOSITION,
+        format!("attachment; filename=\"transcript-{}.md\"", id),
+      ),
+    ],
+    text,
+  )
+    .into_response()
+}
+
+/// The view a room's WebSocket would send the caller, for clients that poll
+async fn room_state(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<Uuid>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  let Some(r_lock) = state.rooms.get(&id) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let room = r_lock.read().await;
+  // Anyone without a seat reads the room as a spectator would
+  let spectate = room.players.get(&user.id).is_none_or(|p| p.is_spectator);
+  if !room.can_enter(user.id, user.is_admin(), spectate) {
+    return (StatusCode::FORBIDDEN, "This room needs a password").into_response();
+  }
+  Json(room.get_view(Some(user.id), user.is_admin())).into_response()
+}
+
+/// Longest text accepted by the decompose API, in chars
+const DECOMPOSE_MAX_CHARS: usize = 100;
+/// Minimum gap between one user's decompose queries
+const DECOMPOSE_MIN_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(serde::Deserialize)]
+struct DecomposeParams {
+  text: String,
+}
+
+async fn decompose_text(
+  State(state): State<Arc<AppState>>,
+  axum::Extension(user): axum::Extension<User>,
+  Query(params): Query<DecomposeParams>,
+) -> Response {
+  let now = Instant::now();
+  if let Some(last) = state.decompose_last.get(&user.id)
+    && now.duration_since(*last) < DECOMPOSE_MIN_INTERVAL
+  {
+    return StatusCode::TOO_MANY_REQUESTS.into_response();
+  }
+  state.decompose_last.insert(user.id, now);
+
+  if params.text.chars().count() > DECOMPOSE_MAX_CHARS {
+    return (
+      StatusCode::BAD_REQUEST,
+      format!("Text is limited to {} characters", DECOMPOSE_MAX_CHARS),
+    )
+      .into_response();
+  }
+  Json(crate::game::pinyin_utils::decompose(
+    &params.text,
+    &state.pinyin_tables().toned,
+  ))
+  .into_response()
+}
+
+async fn leaderboard(State(state): State<Arc<AppState>>) -> Response {
+  Json(state.stats.leaderboard()).into_response()
+}
+
+/// Pick up dictionary edits without a restart. Running games keep the old table.
+async fn reload_dict(
+  State(state): State<Arc<AppState>>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  if user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  match state.reload_pinyin_table() {
+    Ok(count) => format!("Loaded {} characters", count).into_response(),
+    Err(e) => (
+      StatusCode::INTERNAL_SERVER_ERROR,
+      format!("Could not read {}: {}", state.config.dict_path, e),
+    )
+      .into_response(),
+  }
+}
+
+async fn admin_stats(
+  State(state): State<Arc<AppState>>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  if user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  // Snapshot the handles first so no map shard stays locked while awaiting room locks
+  let handles: Vec<_> = state.rooms.iter().map(|r| r.value().clone()).collect();
+
+  let mut stats = AdminStats {
+    total_rooms: handles.len(),
+    rooms_by_phase: Default::default(),
+    players_online: 0,
+    spectators: 0,
+    rooms: vec![],
+  };
+  for r_lock in handles {
+    let r = r_lock.read().await;
+    let phase = r.phase();
+    let players = r.players.values().filter(|p| !p.is_spectator).count();
+    let players_online = r
+      .players
+      .values()
+      .filter(|p| !p.is_spectator && p.is_online)
+      .count();
+    let spectators = r.players.values().filter(|p| p.is_spectator).count();
+
+    *stats.rooms_by_phase.entry(phase.to_string()).or_default() += 1;
+    stats.players_online += players_online;
+    stats.spectators += spectators;
+    stats.rooms.push(RoomStats {
+      id: r.id.to_string(),
+      name: r.name.clone(),
+      room_type: r.room_type,
+      phase,
+      players,
+      players_online,
+      spectators,
+      max_players: r.max_players,
+    });
+  }
+  Json(stats).into_response()
+}
+
+/// Liveness probe for load balancers.
+async fn health(State(state): State<Arc<AppState>>) -> Response {
+  Json(serde_json::json!({
+    "status": "ok",
+    "uptime_secs": state.started_at.elapsed().as_secs(),
+  }))
+  .into_response()
+}
+
+/// Room and player gauges in the Prometheus text format.
+async fn metrics(
+  State(state): State<Arc<AppState>>,
+  axum::Extension(user): axum::Extension<User>,
+) -> Response {
+  if user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  // Snapshot the handles first so no map shard stays locked while awaiting room locks
+  let handles: Vec<_> = state.rooms.iter().map(|r| r.value().clone()).collect();
+  let modes = [RoomType::Chain, RoomType::Pinyin];
+  let mut rooms_by_mode = [0usize; 2];
+  let mut games_by_mode = [0usize; 2];
+  let mut players = 0;
+  let mut spectators = 0;
+  for r_lock in &handles {
+    let r = r_lock.read().await;
+    let mode = match r.room_type {
+      RoomType::Chain => 0,
+      RoomType::Pinyin => 1,
+    };
+    rooms_by_mode[mode] += 1;
+    games_by_mode[mode] += r.is_game_in_progress() as usize;
+    for p in r.players.values().filter(|p| p.is_online) {
+      if p.is_spectator {
+        spectators += 1;
+      } else {
+        players += 1;
+      }
+    }
+  }
+
+  let mut out = format!(
+    "# HELP quiz_time_uptime_seconds Seconds since the server started.\n\
+     # TYPE quiz_time_uptime_seconds counter\n\
+     quiz_time_uptime_seconds {}\n\
+     # HELP quiz_time_players_connected Players connected to a room, by role.\n\
+     # TYPE quiz_time_players_connected gauge\n\
+     quiz_time_players_connected{{role=\"player\"}} {}\n\
+     quiz_time_players_connected{{role=\"spectator\"}} {}\n\
+     # HELP quiz_time_rooms Open rooms, by mode.\n\
+     # TYPE quiz_time_rooms gauge\n",
+    state.started_at.elapsed().as_secs(),
+    players,
+    spectators,
+  );
+  for (mode, count) in modes.iter().zip(rooms_by_mode) {
+    out += &format!("quiz_time_rooms{{mode=\"{}\"}} {}\n", mode, count);
+  }
+  out += "# HELP quiz_time_games_in_progress Games being played, by mode.\n\
+          # TYPE quiz_time_games_in_progress gauge\n";
+  for (mode, count) in modes.iter().zip(games_by_mode) {
+    out += &format!(
+      "quiz_time_games_in_progress{{mode=\"{}\"}} {}\n",
+      mode, count
+    );
+  }
+  ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], out).into_response()
+}
+
+#[derive(serde::Deserialize)]
+struct SetRoleJson {
+  role: Role,
+}
+
+/// Change a user's role. Taking a role away also signs them out everywhere.
+async fn set_user_role(
+  State(state): State<Arc<AppState>>,
+  Path(id): Path<i64>,
+  axum::Extension(user): axum::Extension<User>,
+  Json(payload): Json<SetRoleJson>,
+) -> Response {
+  if user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+  let Some(current) = state.users.get(&id).map(|u| u.role) else {
+    return StatusCode::NOT_FOUND.into_response();
+  };
+  let admins = state.users.iter().filter(|u| u.role == Role::Admin).count();
+  if current == Role::Admin && payload.role != Role::Admin && admins <= 1 {
+    return (StatusCode::CONFLICT, "Can't demote the last admin").into_response();
+  }
+  let demoted = matches!(
+    (current, payload.role),
+    (Role::Admin, Role::Normal | Role::Banned) | (Role::Normal, Role::Banned)
+  );
+  if let Some(mut target) = state.users.get_mut(&id) {
+    target.role = payload.role;
+    if demoted {
+      target.valid_after = chrono::Utc::now().timestamp();
+    }
+  }
+  if let Err(e) = state.save_users() {
+    tracing::error!("failed to save users: {}", e);
+    return (
+      StatusCode::INTERNAL_SERVER_ERROR,
+      "Role changed, but could not be saved",
+    )
+      .into_response();
+  }
+  StatusCode::OK.into_response()
+}
+
+#[derive(serde::Deserialize)]
+struct UserListParams {
+  #[serde(default)]
+  q: String,
+  #[serde(default = "default_page")]
+  page: usize,
+  #[serde(default = "default_per_page")]
+  per_page: usize,
+}
+
+fn default_page() -> usize {
+  1
+}
+
+fn default_per_page() -> usize {
+  50
+}
+
+async fn admin_users(
+  State(state): State<Arc<AppState>>,
+  axum::Extension(user): axum::Extension<User>,
+  Query(params): Query<UserListParams>,
+) -> Response {
+  if user.role != Role::Admin {
+    return StatusCode::FORBIDDEN.into_response();
+  }
+
+  let q = params.q.to_lowercase();
+  let mut users: Vec<UserEntry> = state
+    .users
+    .iter()
+    .filter(|u| u.name.to_lowercase().contains(&q))
+    .map(|u| UserEntry {
+      id: u.id,
+      name: u.name.clone(),
+      role: u.role,
+      valid_after: u.valid_after,
+    })
+    .collect();
+  users.sort_by_key(|u| u.id);
+
+  let page = params.page.max(1);
+  let per_page = params.per_page.clamp(1, 200);
+  let total = users.len();
+  let users = users
+    .into_iter()
+    .skip((page - 1) * per_page)
+    .take(per_page)
+    .collect();
+
+  Json(UserList {
+    total,
+    page,
+    per_page,
+    users,
+  })
+  .into_response()
+}
@@ -1,49 +1,170 @@
 use crate::auth::User;
+use crate::auth::oauth::Provider;
 use crate::conf::Config;
 use crate::game::InternalMsg;
-use crate::game::{pinyin_utils::PinyinTable, room::Room};
-use anyhow::Result;
+use crate::game::{pinyin_utils::PinyinTables, room::Room, snapshot::write_atomic};
+use crate::models::RoomSummary;
+use anyhow::{Context, Result};
 use dashmap::DashMap;
-use std::{fs, sync::Arc};
+use std::sync::RwLock as SyncRwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fs, sync::Arc, time::Instant};
 use tokio::sync::{RwLock, broadcast};
 use uuid::Uuid;
 
+/// Accounts and roles, read on start and rewritten when an admin changes one
+const USERS_FILE: &str = "users.json";
+
 pub struct AppState {
   pub config: Config,
   pub users: DashMap<i64, User>,
   // RwLock 允许对房间进行内部修改，DashMap 处理并发访问
   pub rooms: DashMap<Uuid, Arc<RwLock<Room>>>,
-  pub pinyin_table: Arc<PinyinTable>,
+  /// Swapped whole on reload; games hold on to the table they started with
+  pinyin_tables: SyncRwLock<PinyinTables>,
   // 全局广播通道 (用于系统级通知，房间有自己的通道)
   pub global_tx: broadcast::Sender<InternalMsg>,
   pub oauth_client: crate::auth::oauth::Client,
+  pub github_client: Option<crate::auth::oauth::Client>,
   pub token_manager: crate::auth::token::TokenManager,
+  // 每个用户最近一次查询拆分的时间，用于限流
+  pub decompose_last: DashMap<i64, Instant>,
+  pub stats: crate::game::stats::StatsStore,
+  /// Set when a user changed since the users file was last written
+  users_dirty: AtomicBool,
+  /// For uptime on `/health` and `/metrics`
+  pub started_at: Instant,
 }
 
 impl AppState {
+  /// The dictionary new games are started with.
+  pub fn pinyin_tables(&self) -> PinyinTables {
+    self.pinyin_tables.read().unwrap().clone()
+  }
+
+  /// Read the dictionary again and use it for games started from now on.
+  /// Returns the number of characters loaded; the old table is kept on error.
+  pub fn reload_pinyin_table(&self) -> std::io::Result<usize> {
+    let table = crate::game::pinyin_utils::load_pinyin_table(&self.config.dict_path)?;
+    let count = table.len();
+    *self.pinyin_tables.write().unwrap() = PinyinTables::new(table);
+    tracing::info!(
+      "reloaded {} characters from {}",
+      count,
+      self.config.dict_path
+    );
+    Ok(count)
+  }
+
+  /// Every room as listed in the lobby.
+  pub async fn room_summaries(&self) -> Vec<RoomSummary> {
+    let mut rooms = vec![];
+    for r_lock in self.rooms.iter() {
+      rooms.push(r_lock.read().await.summary());
+    }
+    rooms.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    rooms
+  }
+
+  /// Write every room to the configured file, for the next start to pick up.
+  pub async fn save_rooms(&self) -> Result<()> {
+    let Some(path) = &self.config.rooms_file else {
+      return Ok(());
+    };
+    let locks: Vec<_> = self.rooms.iter().map(|r| r.value().clone()).collect();
+    let mut guards = vec![];
+    for lock in &locks {
+      guards.push(lock.read().await);
+    }
+    let rooms: Vec<&Room> = guards.iter().map(|g| &**g).collect();
+    crate::game::snapshot::save(path, &rooms)?;
+    tracing::info!("saved {} room(s) to {}", rooms.len(), path);
+    Ok(())
+  }
+
+  /// Note a change to `users` for the next `flush_users`, rather than writing
+  /// the file on every logout.
+  pub fn mark_users_changed(&self) {
+    self.users_dirty.store(true, Ordering::Relaxed);
+  }
+
+  /// Write the users file if anything changed since the last write.
+  pub fn flush_users(&self) {
+    if !self.users_dirty.swap(false, Ordering::Relaxed) {
+      return;
+    }
+    if let Err(e) = self.save_users() {
+      tracing::error!("failed to save users: {}", e);
+      self.mark_users_changed();
+    }
+  }
+
+  /// Write every known user back to the users file.
+  pub fn save_users(&self) -> Result<()> {
+    let mut users: Vec<User> = self.users.iter().map(|u| u.clone()).collect();
+    users.sort_by_key(|u| u.id);
+    let json = serde_json::to_string_pretty(&users)?;
+    write_atomic(USERS_FILE, json)?;
+    Ok(())
+  }
+
   pub fn new() -> Result<Self> {
-    let config = Config::load();
+    let config = Config::load()?;
 
-    let users_json = fs::read_to_string("users.json").unwrap();
-    let users_list: Vec<User> = serde_json::from_str(&users_json)?;
+    let users_json = fs::read_to_string(USERS_FILE)
+      .with_context(|| format!("could not read users file {}", USERS_FILE))?;
+    let users_list: Vec<User> = serde_json::from_str(&users_json)
+      .with_context(|| format!("invalid users file {}", USERS_FILE))?;
     let users_map = DashMap::new();
     for u in users_list {
       users_map.insert(u.id, u);
     }
 
-    let pinyin_table = Arc::new(crate::game::pinyin_utils::load_pinyin_table("dict.txt"));
-    let oauth_client = crate::auth::oauth::init_oauth_client(&config);
+    let pinyin_tables = PinyinTables::new(
+      crate::game::pinyin_utils::load_pinyin_table(&config.dict_path).unwrap_or_else(|e| {
+        tracing::warn!("could not read dictionary {}: {}", config.dict_path, e);
+        Default::default()
+      }),
+    );
+    let rooms = DashMap::new();
+    if let Some(path) = &config.rooms_file {
+      match crate::game::snapshot::load(path, &config, &pinyin_tables) {
+        Ok(saved) => {
+          if !saved.is_empty() {
+            tracing::info!("restored {} room(s) from {}", saved.len(), path);
+          }
+          for room in saved {
+            rooms.insert(room.id, Arc::new(RwLock::new(room)));
+          }
+        }
+        Err(e) => tracing::error!("could not restore rooms from {}: {}", path, e),
+      }
+    }
+    let oauth_client =
+      crate::auth::oauth::init_oauth_client(&config, Provider::Codeberg, &config.oauth);
+    let github_client = config
+      .github
+      .as_ref()
+      .map(|gh| crate::auth::oauth::init_oauth_client(&config, Provider::Github, gh));
     let token_manager = crate::auth::token::TokenManager::new();
-    let (tx, _) = broadcast::channel(1);
+    // Lobby updates are only a signal to rebuild the list, so a small buffer is enough
+    let (tx, _) = broadcast::channel(16);
+
+    let stats = crate::game::stats::StatsStore::load(config.stats_file.clone());
 
     Ok(Self {
       config,
       users: users_map,
-      rooms: DashMap::new(),
-      pinyin_table,
+      rooms,
+      pinyin_tables: SyncRwLock::new(pinyin_tables),
       global_tx: tx,
       oauth_client,
+      github_client,
       token_manager,
+      decompose_last: DashMap::new(),
+      stats,
+      users_dirty: AtomicBool::new(false),
+      started_at: Instant::now(),
     })
   }
 }
@@ -1,10 +1,12 @@
 use crate::auth::User;
 use crate::game::{ClientAction, InternalMsg};
+use crate::models::ClientView;
 use crate::state::AppState;
+use axum::body::Bytes;
 use axum::{
   extract::{
     Query, State,
-    ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    ws::{CloseFrame, Message, Utf8Bytes, WebSocket, WebSocketUpgrade},
   },
   response::IntoResponse,
 };
@@ -16,13 +18,80 @@ use std::{
   sync::Arc,
   time::{Duration, Instant},
 };
+use tokio::sync::broadcast;
 use uuid::Uuid;
 
+/// Updates shorter than this go out as plain text: gzip saves too little on
+/// them to be worth the CPU here and the inflate on the client
+const COMPRESS_MIN_BYTES: usize = 1024;
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Optional modes a client may turn on with its `hello`; off until it does.
+const OPTIONAL_CAPABILITIES: &[&str] = &["phase_events"];
+
+/// What one connection has negotiated.
+#[derive(Default)]
+struct Capabilities {
+  phase_events: bool,
+}
+
+impl Capabilities {
+  fn enable(&mut self, modes: &[String]) {
+    for mode in modes {
+      // Unknown modes are ignored so newer clients can talk to older servers
+      if mode == "phase_events" {
+        self.phase_events = true;
+      }
+    }
+  }
+}
+
+/// Room-locking messages one connection may send in a burst, refilled at
+/// `ACTION_RATE` per second. Drafts and hellos don't count.
+const ACTION_BURST: f64 = 10.0;
+const ACTION_RATE: f64 = 8.0;
+
+/// Token bucket limiting how fast a connection can make the room act.
+struct ActionBudget {
+  tokens: f64,
+  last: Instant,
+  /// Already told the client it's being throttled
+  warned: bool,
+}
+
+impl ActionBudget {
+  fn new() -> Self {
+    Self {
+      tokens: ACTION_BURST,
+      last: Instant::now(),
+      warned: false,
+    }
+  }
+
+  fn try_spend(&mut self) -> bool {
+    let now = Instant::now();
+    let refill = now.duration_since(self.last).as_secs_f64() * ACTION_RATE;
+    self.tokens = (self.tokens + refill).min(ACTION_BURST);
+    self.last = now;
+    if self.tokens >= 1.0 {
+      self.tokens -= 1.0;
+      self.warned = false;
+      true
+    } else {
+      false
+    }
+  }
+}
+
 #[derive(serde::Deserialize)]
 pub struct WsParams {
   room: Uuid,
   #[serde(default)]
   spectate: bool,
+  /// Admin only: render views from this player's perspective
+  as_player: Option<i64>,
+  /// Token from the previous connection's hello, to resume without a rejoin
+  resume: Option<Uuid>,
 }
 
 pub async fn ws_handler(
@@ -31,11 +100,51 @@ pub async fn ws_handler(
   Query(params): Query<WsParams>,
   user_ext: Option<axum::Extension<User>>,
 ) -> impl IntoResponse {
-  if let Some(axum::Extension(u)) = user_ext {
-    ws.on_upgrade(move |socket| handle_socket(socket, state, params.room, u, params.spectate))
-  } else {
-    (axum::http::StatusCode::UNAUTHORIZED, "Unauthorized").into_response()
+  let Some(axum::Extension(u)) = user_ext else {
+    return (axum::http::StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+  };
+
+  // Viewing as someone else always joins as a spectator
+  let spectate = params.spectate || params.as_player.is_some();
+
+  if let Some(r_lock) = state.rooms.get(&params.room)
+    && !r_lock.read().await.can_enter(u.id, u.is_admin(), spectate)
+  {
+    return (
+      axum::http::StatusCode::FORBIDDEN,
+      "This room needs a password",
+    )
+      .into_response();
+  }
+
+  if let Some(target) = params.as_player {
+    let is_admin = match state.rooms.get(&params.room) {
+      Some(r_lock) => r_lock.read().await.admin_ids.contains(&u.id) || u.is_admin(),
+      None => false,
+    };
+    if !is_admin {
+      return (axum::http::StatusCode::FORBIDDEN, "Forbidden").into_response();
+    }
+    tracing::info!(
+      target: "audit",
+      "user {} viewing room {} as player {}",
+      u.id,
+      params.room,
+      target
+    );
   }
+
+  ws.on_upgrade(move |socket| {
+    handle_socket(
+      socket,
+      state,
+      params.room,
+      u,
+      spectate,
+      params.as_player,
+      params.resume,
+    )
+  })
 }
 
 async fn handle_socket(
@@ -44,6 +153,8 @@ async fn handle_socket(
   room_id: Uuid,
   user: User,
   req_spectate: bool,
+  as_player: Option<i64>,
+  resume: Option<Uuid>,
 ) {
   let (mut sender, mut receiver) = socket.split();
 
@@ -52,19 +163,20 @@ async fn handle_socket(
   let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
   heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
-  let (rx, _tx) = {
+  let (rx, resume_token) = {
     let r_lock = match state.rooms.get(&room_id) {
       Some(r) => r,
       None => return,
     };
     let mut room = r_lock.write().await;
     match room.join(
-      user.id.clone(),
+      user.id,
       user.name.clone(),
       req_spectate,
       user.is_admin(),
+      resume,
     ) {
-      Ok(rx) => (rx, room.tx.clone()),
+      Ok(joined) => joined,
       Err(e) => {
         let _ = sender
           .send(Message::Close(Some(CloseFrame {
@@ -78,17 +190,32 @@ async fn handle_socket(
   };
 
   let mut broadcast_rx = rx;
+  let is_spectator = req_spectate || as_player.is_some();
+  // Perspective used for views; an admin viewing as a player gets no super view
+  let (view_id, view_super) = match as_player {
+    Some(pid) => (pid, false),
+    None => (user.id, user.is_admin()),
+  };
+
+  let mut caps = Capabilities::default();
+  let mut budget = ActionBudget::new();
+  let hello = serde_json::json!({
+    "type": "hello",
+    "data": {
+      "version": env!("CARGO_PKG_VERSION"),
+      "capabilities": OPTIONAL_CAPABILITIES,
+      "resume_token": resume_token,
+    }
+  });
+  let _ = sender.send(Message::text(hello.to_string())).await;
 
   // Initial State
   {
     if let Some(r_lock) = state.rooms.get(&room_id) {
       let room = r_lock.read().await;
-      let view = room.get_view(Some(user.id), user.is_admin());
-      if let Ok(json) =
-        serde_json::to_string(&serde_json::json!({ "type": "update", "data": view }))
-      {
-        let bin = compress_msg(&json);
-        let _ = sender.send(Message::binary(bin)).await;
+      let bin = render_update(&room.get_view(Some(view_id), view_super));
+      if !bin.is_empty() {
+        let _ = sender.send(update_frame(bin)).await;
       }
     }
   }
@@ -102,14 +229,31 @@ async fn handle_socket(
         match msg {
           Message::Text(text) => {
             // Spectators shouldn't really send actions, but we filter in room logic anyway
-            if let Ok(action) = serde_json::from_str::<ClientAction>(&text) {
-              if let Some(r_lock) = state.rooms.get(&room_id) {
-                let mut room = r_lock.write().await;
-                match action {
-                  ClientAction::Action { action } => room.handle_action(user.id, action),
-                  ClientAction::Answer { content } => room.handle_answer(user.id, content),
+            match serde_json::from_str::<ClientAction>(&text) {
+              Ok(ClientAction::Hello { enable }) => caps.enable(&enable),
+              Ok(ref action) if !matches!(action, ClientAction::Draft { .. }) && !budget.try_spend() => {
+                // Dropped; say so once per burst rather than once per message
+                if !budget.warned {
+                  budget.warned = true;
+                  let json = serde_json::json!({"type": "toast", "data": {"msg": "Slow down: some actions were ignored", "kind": "warning"}});
+                  if sender.send(Message::text(json.to_string())).await.is_err() { break; }
+                }
+              }
+              Ok(action) => {
+                if let Some(r_lock) = state.rooms.get(&room_id) {
+                  let mut room = r_lock.write().await;
+                  match action {
+                    ClientAction::Action { action } => room.handle_action(user.id, action),
+                    ClientAction::Answer { content } => room.handle_answer(user.id, content),
+                    ClientAction::Draft { content } => room.handle_draft(user.id, content),
+                    ClientAction::Ready { ready } => room.set_ready(user.id, ready),
+                    ClientAction::Chat { text } => room.handle_chat(user.id, text),
+                    // Handled by the connection above
+                    ClientAction::Hello { .. } => {}
+                  }
                 }
               }
+              Err(_) => {}
             }
           },
           Message::Pong(_) => {},
@@ -117,16 +261,23 @@ async fn handle_socket(
           _ => {}
         }
       }
-      Ok(msg) = broadcast_rx.recv() => {
+      msg = broadcast_rx.recv() => {
+        let Some((msg, resync)) = next_room_msg(msg, room_id) else {
+          break;
+        };
         match msg {
           InternalMsg::StateUpdated => {
             if let Some(r_lock) = state.rooms.get(&room_id) {
               let room = r_lock.read().await;
-              let view = room.get_view(Some(user.id), user.is_admin());
-              if let Ok(json) = serde_json::to_string(&serde_json::json!({ "type": "update", "data": view })) {
-                let bin = compress_msg(&json);
-                if sender.send(Message::binary(bin)).await.is_err() { break; }
-              }
+              // A resync can come long after the cached frame was built, with its
+              // timers gone stale, so it gets one of its own
+              let bin = if resync {
+                render_update(&room.get_view(Some(view_id), view_super))
+              } else {
+                room.cached_view(view_id, view_super, render_update)
+              };
+              drop(room);
+              if !bin.is_empty() && sender.send(update_frame(bin)).await.is_err() { break; }
             }
           },
           InternalMsg::Log { who, text, time } => {
@@ -140,6 +291,16 @@ async fn handle_socket(
               if sender.send(Message::text(json.to_string())).await.is_err() { break; }
             }
           },
+          InternalMsg::Phase { from, to } if caps.phase_events => {
+            let json = serde_json::json!({"type": "phase", "data": {"from": from, "to": to}});
+            if sender.send(Message::text(json.to_string())).await.is_err() { break; }
+          },
+          InternalMsg::Chat { from, text, time, spectators_only } => {
+            if spectators_only && !is_spectator { continue; }
+            let json = serde_json::json!({"type": "chat", "data": {"from": from, "text": text, "time": time, "spectators_only": spectators_only}});
+            if sender.send(Message::text(json.to_string())).await.is_err() { break; }
+          },
+          InternalMsg::Phase { .. } | InternalMsg::LobbyChanged => {},
           InternalMsg::Kick { target } => {
             if target == user.id {
               let _ = sender.send(Message::Close(Some(CloseFrame {
@@ -165,12 +326,143 @@ async fn handle_socket(
   // Cleanup on disconnect
   if let Some(r_lock) = state.rooms.get(&room_id) {
     let mut room = r_lock.write().await;
-    room.leave(user.id);
+    room.leave(user.id, resume_token);
   }
 }
 
-fn compress_msg(text: &str) -> Vec<u8> {
+pub async fn lobby_handler(
+  State(state): State<Arc<AppState>>,
+  ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+  ws.on_upgrade(move |socket| handle_lobby_socket(socket, state))
+}
+
+/// Stream the room list: once on connect, then whenever it changes.
+async fn handle_lobby_socket(socket: WebSocket, state: Arc<AppState>) {
+  let (mut sender, mut receiver) = socket.split();
+  let mut lobby_rx = state.global_tx.subscribe();
+
+  loop {
+    let rooms = state.room_summaries().await;
+    let json = serde_json::json!({ "type": "rooms", "data": rooms });
+    if sender.send(Message::text(json.to_string())).await.is_err() {
+      break;
+    }
+    // Wait for the next change, skipping anything else on the global channel
+    let changed = loop {
+      tokio::select! {
+        msg = lobby_rx.recv() => match msg {
+          Ok(InternalMsg::LobbyChanged) | Err(broadcast::error::RecvError::Lagged(_)) => break true,
+          Ok(_) => {}
+          Err(broadcast::error::RecvError::Closed) => break false,
+        },
+        msg = receiver.next() => match msg {
+          Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break false,
+          Some(Ok(_)) => {}
+        },
+      }
+    };
+    if !changed {
+      break;
+    }
+  }
+}
+
+/// The next message from the room channel, and whether it is a resync after
+/// falling behind. `None` once the room is gone.
+fn next_room_msg(
+  msg: Result<InternalMsg, broadcast::error::RecvError>,
+  room_id: Uuid,
+) -> Option<(InternalMsg, bool)> {
+  match msg {
+    Ok(msg) => Some((msg, false)),
+    // Whatever was missed, a fresh view puts the client right again
+    Err(broadcast::error::RecvError::Lagged(n)) => {
+      tracing::debug!(
+        "room {} connection lagged by {} message(s), resyncing",
+        room_id,
+        n
+      );
+      Some((InternalMsg::StateUpdated, true))
+    }
+    Err(broadcast::error::RecvError::Closed) => None,
+  }
+}
+
+/// An `update` message for `view`, ready for `update_frame`; empty if it can't
+/// be serialized.
+fn render_update(view: &ClientView) -> Bytes {
+  serde_json::to_string(&serde_json::json!({ "type": "update", "data": view }))
+    .map(|json| compress_msg(&json))
+    .unwrap_or_default()
+}
+
+/// Gzip an update if it's long enough to be worth it; otherwise keep the JSON as is.
+fn compress_msg(text: &str) -> Bytes {
+  if text.len() < COMPRESS_MIN_BYTES {
+    return Bytes::copy_from_slice(text.as_bytes());
+  }
   let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
   encoder.write_all(text.as_bytes()).unwrap();
-  encoder.finish().unwrap()
+  encoder.finish().unwrap().into()
+}
+
+/// Gzipped updates go in binary frames, which tells the client to inflate them;
+/// JSON can't start with the gzip magic, so plain ones are sent as text.
+fn update_frame(payload: Bytes) -> Message {
+  if payload.starts_with(&GZIP_MAGIC) {
+    return Message::Binary(payload);
+  }
+  Message::Text(Utf8Bytes::try_from(payload).expect("updates are JSON"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn log(text: &str) -> InternalMsg {
+    InternalMsg::Log {
+      who: "System".into(),
+      text: text.into(),
+      time: String::new(),
+    }
+  }
+
+  #[tokio::test]
+  async fn lagging_behind_resyncs_and_carries_on() {
+    let (tx, mut rx) = broadcast::channel(2);
+    for i in 0..5 {
+      tx.send(log(&i.to_string())).unwrap();
+    }
+    let room = Uuid::nil();
+    let (msg, resync) = next_room_msg(rx.recv().await, room).unwrap();
+    assert!(resync && matches!(msg, InternalMsg::StateUpdated));
+    // Then the messages still buffered, in order
+    for want in ["3", "4"] {
+      let (msg, resync) = next_room_msg(rx.recv().await, room).unwrap();
+      assert!(!resync && matches!(msg, InternalMsg::Log { text, .. } if text == want));
+    }
+  }
+
+  #[tokio::test]
+  async fn closed_room_ends_the_connection() {
+    let (tx, mut rx) = broadcast::channel(2);
+    tx.send(log("last")).unwrap();
+    drop(tx);
+    assert!(next_room_msg(rx.recv().await, Uuid::nil()).is_some());
+    assert!(next_room_msg(rx.recv().await, Uuid::nil()).is_none());
+  }
+
+  #[test]
+  fn updates_are_compressed_only_when_long() {
+    let short = r#"{"type":"update"}"#;
+    assert!(matches!(update_frame(compress_msg(short)), Message::Text(t) if t.as_str() == short));
+    let long = format!(
+      r#"{{"type":"update","data":"{}"}}"#,
+      "x".repeat(COMPRESS_MIN_BYTES)
+    );
+    assert!(
+      matches!(update_frame(compress_msg(&long)), Message::Binary(b) if b.starts_with(&GZIP_MAGIC))
+    );
+  }
 }